@@ -0,0 +1,246 @@
+use crate::types::{ContractCategory, ContractKind, SettleCurrency};
+use crate::instruments::{self, Instrument};
+use crate::utils;
+
+use regex::Regex;
+use rust_decimal::Decimal;
+
+use std::sync::OnceLock;
+
+/// Decimal places of size of inverse position, which is divided by price
+const SIZE_DECIMAL_PLACES: u32 = 8;
+
+/// Multiplier prefix of base currency of linear symbol e.g. 1000PEPE
+static MULTIPLIER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Specification of contract needed to value its positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractSpec {
+    /// Category of contract
+    pub category: ContractCategory,
+
+    /// Coin position is in e.g. BTC, or PEPE for 1000PEPEUSDT
+    pub base_coin: String,
+
+    /// Currency price is quoted in e.g. USD, or USDT
+    pub quote_currency: String,
+
+    /// Currency margin, and PnL are settled in e.g. BTC for BTCUSD, or USDT
+    /// for BTCUSDT
+    pub settle_currency: SettleCurrency,
+
+    /// Unit quantity is denominated in; USD contracts for inverse, base coin
+    /// for linear e.g. BTC, or 1000PEPE for 1000PEPEUSDT
+    pub qty_unit: String,
+
+    /// For inverse, value of a contract in quote currency. For linear, base
+    /// coins in a unit of quantity e.g. 1000 for 1000PEPEUSDT.
+    pub multiplier: Decimal,
+}
+
+/// Value of a position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Valuation {
+    /// Notional value in USD
+    pub notional_usd: Decimal,
+
+    /// Size in base coin
+    pub size: Decimal,
+}
+
+impl ContractSpec {
+    /// Get specification of contract of the symbol from instrument registry,
+    /// or derive it from the symbol if registry doesn't know it.
+    ///
+    /// # Arguments
+    /// * `symbol` - fully qualified symbol
+    pub fn from_symbol(symbol: &str) -> Self {
+        instruments::lookup(symbol)
+            .and_then(|instrument| Self::from_instrument(&instrument))
+            .unwrap_or_else(|| Self::from_symbol_heuristics(symbol))
+    }
+
+    /// Get specification of contract from instrument of registry.
+    ///
+    /// # Returns
+    /// `None` if type of contract is not known.
+    fn from_instrument(instrument: &Instrument) -> Option<Self> {
+        let category = instrument.category()?;
+        let spec = match category {
+            ContractCategory::LinearPerpetual | ContractCategory::LinearFutures => {
+                let (multiplier, base_coin) = split_multiplier(&instrument.base_coin);
+                Self {
+                    category,
+                    base_coin,
+                    quote_currency: instrument.quote_coin.clone(),
+                    settle_currency: SettleCurrency::from(instrument.settle_coin.as_str()),
+                    qty_unit: instrument.base_coin.clone(),
+                    multiplier,
+                }
+            },
+            ContractCategory::InversePerpetual | ContractCategory::InverseFutures => Self {
+                category,
+                base_coin: instrument.base_coin.clone(),
+                quote_currency: instrument.quote_coin.clone(),
+                settle_currency: SettleCurrency::from(instrument.settle_coin.as_str()),
+                qty_unit: instrument.quote_coin.clone(),
+                multiplier: Decimal::ONE,
+            },
+        };
+        Some(spec)
+    }
+
+    /// Derive specification of contract from form of its symbol.
+    ///
+    /// On Bybit, quantity of inverse contracts (BTCUSD, BTCUSDM22) is the
+    /// number of 1 USD contracts settled in base coin, while that of linear
+    /// contracts (BTCUSDT) is in base coin settled in USDT. Linear symbols of
+    /// low-priced coins are prefixed by multiplier e.g. 1000PEPEUSDT whose
+    /// quantity, and price are of 1000 PEPE.
+    ///
+    /// # Arguments
+    /// * `symbol` - fully qualified symbol
+    fn from_symbol_heuristics(symbol: &str) -> Self {
+        let category = utils::get_contract_category(symbol);
+        let base = utils::get_base_currency(symbol).unwrap_or("UNKNOWN").to_owned();
+
+        match category {
+            ContractCategory::LinearPerpetual | ContractCategory::LinearFutures => {
+                let (multiplier, base_coin) = split_multiplier(&base);
+                // USDC ones are e.g. BTCPERP, BTC-27DEC24 whose pair has no USDT
+                let pair = symbol.split_once('-').map_or(symbol, |(pair, _)| pair);
+                let quote = if pair.ends_with("USDT") { "USDT" } else { "USDC" };
+                Self {
+                    category,
+                    base_coin,
+                    quote_currency: quote.to_owned(),
+                    settle_currency: SettleCurrency::from(quote),
+                    qty_unit: base,
+                    multiplier,
+                }
+            },
+            ContractCategory::InversePerpetual | ContractCategory::InverseFutures => Self {
+                category,
+                base_coin: base.clone(),
+                quote_currency: "USD".to_owned(),
+                settle_currency: SettleCurrency::Coin(base),
+                qty_unit: "USD".to_owned(),
+                multiplier: Decimal::ONE,
+            },
+        }
+    }
+
+    /// Get the kind of contract.
+    pub fn kind(&self) -> ContractKind {
+        ContractKind::new(self.category, &self.settle_currency)
+    }
+
+    /// Value a position of the contract. USDT, and USDC are taken as USD.
+    ///
+    /// # Arguments
+    /// * `price` - price in quote currency per unit of quantity
+    /// * `qty` - quantity
+    pub fn value(&self, price: Decimal, qty: Decimal) -> Valuation {
+        match self.category {
+            ContractCategory::LinearPerpetual | ContractCategory::LinearFutures => Valuation {
+                notional_usd: (qty * price).normalize(),
+                size: (qty * self.multiplier).normalize(),
+            },
+            ContractCategory::InversePerpetual | ContractCategory::InverseFutures => {
+                let notional_usd = (qty * self.multiplier).normalize();
+                let size = notional_usd.checked_div(price).unwrap_or_default();
+                Valuation {
+                    notional_usd,
+                    size: size.round_dp(SIZE_DECIMAL_PLACES).normalize(),
+                }
+            },
+        }
+    }
+}
+
+/// Split multiplier prefix from base currency of linear symbol e.g. 1000PEPE
+/// into 1000, and PEPE. Only powers of ten from 10 are taken as multiplier,
+/// so coins whose name starts with digit e.g. 1INCH are kept as is.
+///
+/// # Arguments
+/// * `base` - base currency as in symbol
+fn split_multiplier(base: &str) -> (Decimal, String) {
+    let regex = MULTIPLIER_REGEX.get_or_init(|| Regex::new(r"^(10+)(\D\S*)$").unwrap());
+    match regex.captures(base) {
+        Some(captures) => (captures[1].parse::<Decimal>().unwrap_or(Decimal::ONE), captures[2].to_owned()),
+        None => (Decimal::ONE, base.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instrument(symbol: &str, contract_type: &str, base_coin: &str, quote_coin: &str, settle_coin: &str) -> Instrument {
+        Instrument {
+            symbol: symbol.to_owned(),
+            contract_type: contract_type.to_owned(),
+            base_coin: base_coin.to_owned(),
+            quote_coin: quote_coin.to_owned(),
+            settle_coin: settle_coin.to_owned(),
+        }
+    }
+
+    #[test]
+    fn heuristics_agree_with_registry() {
+        let instruments = [
+            instrument("BTCUSD", "InversePerpetual", "BTC", "USD", "BTC"),
+            instrument("BTCUSDM22", "InverseFutures", "BTC", "USD", "BTC"),
+            instrument("ETHUSD0325", "InverseFutures", "ETH", "USD", "ETH"),
+            instrument("BTCUSDT", "LinearPerpetual", "BTC", "USDT", "USDT"),
+            instrument("1000PEPEUSDT", "LinearPerpetual", "1000PEPE", "USDT", "USDT"),
+            instrument("1INCHUSDT", "LinearPerpetual", "1INCH", "USDT", "USDT"),
+            instrument("BTCUSDT-27DEC24", "LinearFutures", "BTC", "USDT", "USDT"),
+            instrument("BTCPERP", "LinearPerpetual", "BTC", "USDC", "USDC"),
+            instrument("BTC-27DEC24", "LinearFutures", "BTC", "USDC", "USDC"),
+        ];
+        for instrument in instruments {
+            assert_eq!(
+                ContractSpec::from_symbol_heuristics(&instrument.symbol),
+                ContractSpec::from_instrument(&instrument).unwrap(),
+                "{}", instrument.symbol);
+        }
+    }
+
+    #[test]
+    fn heuristics_of_dated_usdt_futures() {
+        let spec = ContractSpec::from_symbol_heuristics("BTCUSDT-27DEC24");
+        assert_eq!(spec.kind(), ContractKind::LinearFutures);
+        assert_eq!(spec.base_coin, "BTC");
+        assert_eq!(spec.quote_currency, "USDT");
+        assert_eq!(spec.settle_currency, SettleCurrency::Usdt);
+    }
+
+    #[test]
+    fn registry_of_unknown_contract_type_is_left_to_heuristics() {
+        assert_eq!(ContractSpec::from_instrument(&instrument("BTCUSDT", "LinearOption", "BTC", "USDT", "USDT")), None);
+    }
+
+    #[test]
+    fn multiplier_is_split_from_power_of_ten_only() {
+        assert_eq!(split_multiplier("1000PEPE"), (Decimal::from(1000), "PEPE".to_owned()));
+        assert_eq!(split_multiplier("10000LADYS"), (Decimal::from(10000), "LADYS".to_owned()));
+        assert_eq!(split_multiplier("1INCH"), (Decimal::ONE, "1INCH".to_owned()));
+        assert_eq!(split_multiplier("BTC"), (Decimal::ONE, "BTC".to_owned()));
+    }
+
+    #[test]
+    fn value_of_linear_and_inverse() {
+        let linear = ContractSpec::from_symbol_heuristics("1000PEPEUSDT");
+        assert_eq!(linear.value(Decimal::new(12, 3), Decimal::from(500)), Valuation {
+            notional_usd: Decimal::from(6),
+            size: Decimal::from(500_000),
+        });
+
+        let inverse = ContractSpec::from_symbol_heuristics("BTCUSD");
+        assert_eq!(inverse.value(Decimal::from(64000), Decimal::from(25000)), Valuation {
+            notional_usd: Decimal::from(25000),
+            size: Decimal::new(390625, 6),
+        });
+    }
+}
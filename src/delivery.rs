@@ -0,0 +1,696 @@
+use crate::types::{OperationError, DeliveryConfig, BacklogPolicy, TimeDisplay};
+use crate::ratelimit::RateLimiter;
+use crate::outbox::{self, Outbox, OutboxHandle, OutboxEntry};
+use crate::sinks::{NotificationSink, SinkError};
+use crate::filter::SinkFilter;
+use crate::liquidation::Liquidation;
+use crate::utils;
+
+use rust_decimal::Decimal;
+use tokio::sync::mpsc::{self, Sender, Receiver};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::Instant;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Sequence of delivery ids of notifications not persisted in outbox
+static NEXT_LOCAL_DELIVERY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Notification to be delivered.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Notification {
+    /// Text to send
+    pub text: String,
+
+    /// Short summary to print onto console once delivered
+    pub summary: String,
+
+    /// Worth in USD, used to prioritize when backlog is full
+    pub worth: Decimal,
+
+    /// Time of liquidation as displayed to the sink; empty for notification
+    /// not of a single liquidation
+    #[serde(default)]
+    pub event_time: String,
+
+    /// Liquidation for sinks able to render rich messages, and to filter by;
+    /// `None` for notification not of a single liquidation e.g. summary, or
+    /// merged.
+    pub details: Option<Liquidation>,
+
+    /// Id of notification in delivery, the same across its retries, and
+    /// replay from outbox, e.g. for sink to de-duplicate by; set once it is
+    /// enqueued, empty until then.
+    #[serde(skip)]
+    pub delivery_id: String,
+}
+
+/// Counters of delivery.
+#[derive(Debug, Default)]
+pub struct DeliveryStats {
+    /// Number of notifications delivered
+    pub sent: AtomicU64,
+
+    /// Number of notifications failed to be delivered
+    pub failed: AtomicU64,
+
+    /// Number of notifications dropped as queue is full
+    pub dropped: AtomicU64,
+
+    /// Number of notifications discarded as they are too old to be sent
+    pub stale: AtomicU64,
+
+    /// Number of notifications waiting in queue
+    pub queue_depth: AtomicU64,
+
+    /// Latency of the last send in milliseconds
+    pub last_latency_ms: AtomicU64,
+
+    /// Maximum latency of send in milliseconds so far
+    pub max_latency_ms: AtomicU64,
+}
+
+/// Handle to enqueue notifications for the delivery task of a sink.
+#[derive(Clone)]
+pub struct DeliveryHandle {
+    sink: Arc<dyn NotificationSink>,
+    filter: SinkFilter,
+    time_display: TimeDisplay,
+    tx: Sender<PendingNotification>,
+    outbox: Option<OutboxHandle>,
+
+    /// Counters
+    pub stats: Arc<DeliveryStats>,
+}
+
+impl DeliveryHandle {
+    /// Enqueue notification without waiting.
+    /// It is queued to be persisted into outbox (if any) first, whose disk
+    /// I/O is done by its writer thread; it may be sent before it is synced
+    /// to disk. If queue is full, notification is dropped so caller is never
+    /// blocked by slow delivery.
+    ///
+    /// # Arguments
+    /// * `notification` - notification to enqueue
+    ///
+    /// # Returns
+    /// `Err` with `OperationError::ErrorInternalSyncCommunication` if the
+    /// delivery task is gone, or queue is full. `Err` with
+    /// `OperationError::ErrorOutboxIo` if it cannot be persisted; it is
+    /// still enqueued in such case.
+    pub fn enqueue(&self, notification: Notification) -> Result<(), OperationError> {
+        let mut persist_res = Ok(());
+        let pending = match self.outbox.as_ref().map(|outbox| outbox.add(self.sink.name(), &notification)) {
+            Some(Ok(entry)) => PendingNotification::from(entry),
+            Some(Err(e)) => {
+                persist_res = Err(e);
+                PendingNotification::new(notification, outbox::now_ms())
+            },
+            None => PendingNotification::new(notification, outbox::now_ms()),
+        };
+
+        // count it first, delivery task might dequeue it before we get back
+        self.stats.queue_depth.fetch_add(1, Ordering::Relaxed);
+        match self.tx.try_send(pending) {
+            Ok(_) => persist_res,
+            Err(TrySendError::Full(pending)) => {
+                self.stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                settle_outbox(&self.outbox, &pending.outbox_ids, false);
+                let summary = pending.notification.summary.clone();
+                // dead-letter might do I/O, caller is not to wait for it
+                let sink = self.sink.clone();
+                tokio::spawn(async move {
+                    sink.dead_letter(&pending.notification, "delivery queue is full").await;
+                });
+                ret_err!(OperationError::ErrorInternalSyncCommunication, "delivery queue is full, dropped: {}", summary);
+            },
+            Err(TrySendError::Closed(_)) => {
+                self.stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                ret_err!(OperationError::ErrorInternalSyncCommunication, "delivery task is gone");
+            },
+        }
+    }
+}
+
+impl DeliveryHandle {
+    /// Get the name of sink this handle delivers to.
+    pub fn sink_name(&self) -> &str {
+        self.sink.name()
+    }
+}
+
+/// Dispatcher fanning every notification out to delivery tasks of all
+/// configured sinks.
+/// Enqueueing never waits, so a sink falling behind, or failing only affects
+/// its own queue.
+pub struct Dispatcher {
+    handles: Vec<DeliveryHandle>,
+}
+
+impl Dispatcher {
+    /// Spawn a delivery task for each sink.
+    /// Entries replayed from outbox go back to the sink they were added for,
+    /// those of sinks no longer configured are discarded.
+    ///
+    /// # Arguments
+    /// * `sinks` - sinks along with their configuration of delivery
+    /// * `capacity` - capacity of queue of each sink
+    /// * `outbox` - durable outbox to persist notifications into, if any
+    /// * `replayed` - un-acknowledged entries from outbox to be sent first
+    pub fn spawn(sinks: Vec<(Arc<dyn NotificationSink>, DeliveryConfig)>, capacity: usize, outbox: Option<Outbox>, mut replayed: Vec<OutboxEntry>) -> Self {
+        let outbox = outbox.map(Outbox::spawn_writer);
+
+        let handles = sinks.into_iter()
+            .map(|(sink, config)| {
+                let (own, others) = replayed.drain(..).partition(|entry: &OutboxEntry| entry.sink == sink.name());
+                replayed = others;
+                spawn_delivery(sink, capacity, config, outbox.clone(), own)
+            })
+            .collect();
+
+        if !replayed.is_empty() {
+            eprintln!("Discarded {} notification(s) in outbox of sinks no longer configured", replayed.len());
+            let ids: Vec<u64> = replayed.iter().map(|entry| entry.id).collect();
+            settle_outbox(&outbox, &ids, false);
+        }
+
+        Self { handles }
+    }
+
+    /// Get handles of delivery task of all sinks.
+    pub fn handles(&self) -> &[DeliveryHandle] {
+        &self.handles
+    }
+
+    /// Enqueue notification to all sinks whose filter it passes without
+    /// waiting. Notification is formed for each sink as its time is displayed
+    /// as configured for the sink. Failure to enqueue to a sink is logged, and
+    /// doesn't affect the others.
+    ///
+    /// # Arguments
+    /// * `form` - function forming notification with time display of sink
+    pub fn dispatch<F>(&self, form: F)
+    where
+        F: Fn(&TimeDisplay) -> Notification
+    {
+        for handle in &self.handles {
+            let notification = form(&handle.time_display);
+            if !handle.filter.matches(&notification) {
+                continue;
+            }
+            if let Err(e) = handle.enqueue(notification) {
+                eprintln!("[{}] {}", handle.sink_name(), e);
+            }
+        }
+    }
+}
+
+/// Spawn the delivery task sending notifications to the sink.
+/// Notifications are sent in order they are enqueued, limited by rate of
+/// the sink.
+///
+/// # Arguments
+/// * `sink` - sink to send notifications to
+/// * `capacity` - capacity of queue
+/// * `config` - configuration of delivery
+/// * `outbox` - durable outbox to persist notifications into, if any
+/// * `replayed` - un-acknowledged entries from outbox to be sent first
+pub fn spawn_delivery(sink: Arc<dyn NotificationSink>, capacity: usize, config: DeliveryConfig, outbox: Option<OutboxHandle>, replayed: Vec<OutboxEntry>) -> DeliveryHandle {
+    let (tx, rx) = mpsc::channel::<PendingNotification>(capacity);
+    let stats = Arc::new(DeliveryStats::default());
+    let filter = config.filter.clone();
+    let time_display = config.time_display.clone();
+
+    let backlog: VecDeque<PendingNotification> = replayed.into_iter().map(PendingNotification::from).collect();
+    if !backlog.is_empty() {
+        println!("[{}] Replaying {} notification(s) from outbox", sink.name(), backlog.len());
+    }
+    stats.queue_depth.fetch_add(backlog.len() as u64, Ordering::Relaxed);
+
+    tokio::spawn(run_delivery(sink.clone(), rx, backlog, stats.clone(), config, outbox.clone()));
+    DeliveryHandle { sink, filter, time_display, tx, outbox, stats }
+}
+
+/// Notification waiting to be sent
+struct PendingNotification {
+    notification: Notification,
+
+    /// Number of failed attempts so far
+    attempts: u32,
+
+    /// Timestamp in milliseconds when notification was created; the oldest
+    /// one if merged
+    created_at_ms: u64,
+
+    /// Ids of entries in outbox this notification is made of
+    outbox_ids: Vec<u64>,
+}
+
+impl PendingNotification {
+    fn new(mut notification: Notification, created_at_ms: u64) -> Self {
+        // not persisted, so it only has to be unique within the process
+        notification.delivery_id = format!("local-{}-{}", created_at_ms, NEXT_LOCAL_DELIVERY_ID.fetch_add(1, Ordering::Relaxed));
+        Self { notification, attempts: 0, created_at_ms, outbox_ids: Vec::new() }
+    }
+}
+
+impl From<OutboxEntry> for PendingNotification {
+    fn from(entry: OutboxEntry) -> Self {
+        let mut notification = entry.notification;
+        // ids of outbox start over once it is empty, creation time tells apart
+        notification.delivery_id = format!("outbox-{}-{}", entry.created_at_ms, entry.id);
+        Self { notification, attempts: 0, created_at_ms: entry.created_at_ms, outbox_ids: vec![entry.id] }
+    }
+}
+
+/// Notifications removed from backlog to be summarized
+#[derive(Default)]
+struct Skipped {
+    count: u64,
+    worth: Decimal,
+}
+
+/// Deliver notifications from queue until all senders are gone, and backlog
+/// is drained.
+async fn run_delivery(sink: Arc<dyn NotificationSink>, mut rx: Receiver<PendingNotification>, mut backlog: VecDeque<PendingNotification>, stats: Arc<DeliveryStats>, config: DeliveryConfig, outbox: Option<OutboxHandle>) {
+    let name = sink.name().to_owned();
+    let mut limiter = RateLimiter::new(config.rate_per_minute, config.burst);
+    // services limit rate by destination e.g. by chat of telegram
+    let bucket_key = format!("{}:{}", name, sink.destination());
+    let mut skipped: Option<Skipped> = None;
+    let mut rx_closed = false;
+
+    loop {
+        if backlog.is_empty() {
+            if let Some(skipped) = skipped.take() {
+                stats.queue_depth.fetch_add(1, Ordering::Relaxed);
+                backlog.push_back(PendingNotification::new(summary_notification(&skipped), outbox::now_ms()));
+            }
+            else if rx_closed {
+                break;
+            }
+        }
+
+        let ready_at = limiter.bucket(&bucket_key).ready_at();
+        tokio::select! {
+            item = rx.recv(), if !rx_closed => {
+                match item {
+                    Some(pending) => {
+                        backlog.push_back(pending);
+                        for removed in enforce_backlog_bound(&mut backlog, &mut skipped, &config, &stats, &outbox, sink.as_ref()) {
+                            sink.dead_letter(&removed.notification, "backlog is full").await;
+                        }
+                    },
+                    None => rx_closed = true,
+                }
+            }
+            _ = tokio::time::sleep_until(ready_at), if !backlog.is_empty() => {
+                // stale one is discarded before taking token, so the token is
+                // left for the next one
+                if backlog.front().is_some_and(|pending| outbox::is_stale(pending.created_at_ms, config.max_age)) {
+                    if let Some(pending) = backlog.pop_front() {
+                        stats.stale.fetch_add(1, Ordering::Relaxed);
+                        stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                        settle_outbox(&outbox, &pending.outbox_ids, false);
+                        eprintln!("[{}] Discarded stale notification: {}", name, pending.notification.summary);
+                        sink.dead_letter(&pending.notification, "stale").await;
+                    }
+                    continue;
+                }
+
+                let bucket = limiter.bucket(&bucket_key);
+                if !bucket.try_acquire() {
+                    continue;
+                }
+
+                let mut pending = match backlog.pop_front() {
+                    Some(pending) => pending,
+                    None => continue,
+                };
+                let started = Instant::now();
+                let res = sink.send(&pending.notification).await;
+                let latency_ms = started.elapsed().as_millis() as u64;
+                stats.last_latency_ms.store(latency_ms, Ordering::Relaxed);
+                stats.max_latency_ms.fetch_max(latency_ms, Ordering::Relaxed);
+
+                let e = match res {
+                    Ok(_) => {
+                        stats.sent.fetch_add(1, Ordering::Relaxed);
+                        settle_outbox(&outbox, &pending.outbox_ids, true);
+                        let depth = stats.queue_depth.fetch_sub(1, Ordering::Relaxed) - 1;
+                        println!("[{}] Notified event: {} (latency={}ms, queue_depth={})", name, pending.notification.summary, latency_ms, depth);
+                        continue;
+                    },
+                    Err(e) => e,
+                };
+
+                match e {
+                    SinkError::RetryAfter(retry_after) => {
+                        eprintln!("[{}] {}", name, e);
+                        bucket.pause_for(retry_after);
+                        backlog.push_front(pending);
+                    },
+                    SinkError::Transient(_) if pending.attempts + 1 < config.max_attempts => {
+                        pending.attempts += 1;
+                        let delay = config.retry_backoff.delay(pending.attempts);
+                        eprintln!("[{}] {} (attempt {}/{}, retry in {:?})", name, e, pending.attempts, config.max_attempts, delay);
+                        bucket.pause_for(delay);
+                        backlog.push_front(pending);
+                    },
+                    _ => {
+                        stats.failed.fetch_add(1, Ordering::Relaxed);
+                        sink.dead_letter(&pending.notification, &e.to_string()).await;
+                        settle_outbox(&outbox, &pending.outbox_ids, false);
+                        let depth = stats.queue_depth.fetch_sub(1, Ordering::Relaxed) - 1;
+                        eprintln!("[{}] {} (gave up: {}, latency={}ms, queue_depth={})", name, e, pending.notification.summary, latency_ms, depth);
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Apply backlog policy until backlog is within its bound.
+///
+/// # Returns
+/// Notifications removed from backlog, to be dead-lettered.
+fn enforce_backlog_bound(backlog: &mut VecDeque<PendingNotification>, skipped: &mut Option<Skipped>, config: &DeliveryConfig, stats: &DeliveryStats, outbox: &Option<OutboxHandle>, sink: &dyn NotificationSink) -> Vec<PendingNotification> {
+    let mut removed_all = Vec::new();
+    // consumers of lossless sink rely on every event, whatever is configured
+    let configured = if sink.is_lossless() { BacklogPolicy::DeadLetter } else { config.backlog_policy };
+    while backlog.len() > config.backlog_max.max(1) {
+        let policy = match configured {
+            BacklogPolicy::Merge if merge_adjacent(backlog, sink.max_message_len()) => {
+                stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+                continue;
+            },
+            // nothing can be merged anymore without exceeding message length
+            BacklogPolicy::Merge => BacklogPolicy::DropSmallest,
+            policy => policy,
+        };
+
+        let index = match policy {
+            BacklogPolicy::DeadLetter => 0,
+            _ => match backlog.iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.notification.worth.cmp(&b.notification.worth))
+                .map(|(i, _)| i) {
+                Some(i) => i,
+                None => break,
+            },
+        };
+        let removed = match backlog.remove(index) {
+            Some(removed) => removed,
+            None => break,
+        };
+        stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        settle_outbox(outbox, &removed.outbox_ids, false);
+
+        if policy == BacklogPolicy::Summarize {
+            let skipped = skipped.get_or_insert_with(Skipped::default);
+            skipped.count += 1;
+            skipped.worth += removed.notification.worth;
+        }
+        else {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            eprintln!("[{}] Backlog is full, dropped: {}", sink.name(), removed.notification.summary);
+        }
+        removed_all.push(removed);
+    }
+    removed_all
+}
+
+/// Merge the first pair of adjacent notifications whose combined text fits
+/// in a single message of the sink.
+///
+/// # Arguments
+/// * `backlog` - backlog
+/// * `max_len` - maximum length of a single message in characters
+///
+/// # Returns
+/// True if a pair is merged, otherwise false.
+fn merge_adjacent(backlog: &mut VecDeque<PendingNotification>, max_len: usize) -> bool {
+    let index = match (0..backlog.len().saturating_sub(1))
+        .find(|&i| backlog[i].notification.text.chars().count() + 1 + backlog[i + 1].notification.text.chars().count() <= max_len) {
+        Some(i) => i,
+        None => return false,
+    };
+
+    let next = match backlog.remove(index + 1) {
+        Some(next) => next,
+        None => return false,
+    };
+    let merged = &mut backlog[index];
+    merged.notification.text = format!("{}\n{}", merged.notification.text, next.notification.text);
+    merged.notification.summary = format!("{}; {}", merged.notification.summary, next.notification.summary);
+    merged.notification.worth += next.notification.worth;
+    merged.notification.event_time = String::new();
+    merged.notification.details = None;
+    merged.notification.delivery_id = format!("{}+{}", merged.notification.delivery_id, next.notification.delivery_id);
+    merged.created_at_ms = merged.created_at_ms.min(next.created_at_ms);
+    merged.outbox_ids.extend(next.outbox_ids);
+    true
+}
+
+/// Acknowledge, or discard entries in outbox (if any) once notification is
+/// settled.
+///
+/// # Arguments
+/// * `outbox` - outbox, if any
+/// * `ids` - ids of entries
+/// * `sent` - true to acknowledge as sent, false to discard
+fn settle_outbox(outbox: &Option<OutboxHandle>, ids: &[u64], sent: bool) {
+    let outbox = match outbox {
+        Some(outbox) => outbox,
+        None => return,
+    };
+    for &id in ids {
+        let res = if sent { outbox.ack(id) } else { outbox.discard(id) };
+        if let Err(e) = res {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+/// Form the summary notification of skipped notifications.
+fn summary_notification(skipped: &Skipped) -> Notification {
+    let worth_str = utils::format_worth(skipped.worth);
+    Notification {
+        text: format!("Bybit shiprekt {count} more positions (worth ${worth} in total) not shown individually due to rate limit",
+                      count=skipped.count,
+                      worth=worth_str),
+        summary: format!("summary of {} skipped positions worth ${}", skipped.count, worth_str),
+        worth: skipped.worth,
+        event_time: String::new(),
+        details: None,
+        delivery_id: String::new(),
+    }
+}
+
+/// Periodically log counters, and health of delivery of a sink.
+///
+/// # Arguments
+/// * `handle` - handle of delivery task of the sink
+/// * `interval` - interval of logging
+pub async fn log_stats(handle: DeliveryHandle, interval: Duration) {
+    let stats = &handle.stats;
+    let mut interval = tokio::time::interval(interval);
+    // skip the immediate first tick, there is nothing to report yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        println!("[{}] Delivery stats: health={}, sent={}, failed={}, dropped={}, stale={}, queue_depth={}, last_latency={}ms, max_latency={}ms",
+                 handle.sink_name(),
+                 handle.sink.health().await,
+                 stats.sent.load(Ordering::Relaxed),
+                 stats.failed.load(Ordering::Relaxed),
+                 stats.dropped.load(Ordering::Relaxed),
+                 stats.stale.load(Ordering::Relaxed),
+                 stats.queue_depth.load(Ordering::Relaxed),
+                 stats.last_latency_ms.load(Ordering::Relaxed),
+                 stats.max_latency_ms.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::SymbolFilter;
+    use crate::supervisor::BackoffPolicy;
+    use crate::sinks::SinkHealth;
+
+    use async_trait::async_trait;
+
+    use std::str::FromStr;
+
+    /// Sink recording what it is asked to do
+    #[derive(Default)]
+    struct RecordingSink {
+        /// Error to fail every send with, if any
+        error: Option<SinkError>,
+        lossless: bool,
+        sent: std::sync::Mutex<Vec<String>>,
+        dead_lettered: std::sync::Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl NotificationSink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+            match &self.error {
+                Some(e) => Err(e.clone()),
+                None => {
+                    self.sent.lock().unwrap().push(notification.text.clone());
+                    Ok(())
+                },
+            }
+        }
+
+        async fn health(&self) -> SinkHealth {
+            SinkHealth::Healthy
+        }
+
+        fn is_lossless(&self) -> bool {
+            self.lossless
+        }
+
+        async fn dead_letter(&self, notification: &Notification, reason: &str) {
+            self.dead_lettered.lock().unwrap().push((notification.text.clone(), reason.to_owned()));
+        }
+    }
+
+    fn config(backlog_max: usize, backlog_policy: BacklogPolicy) -> DeliveryConfig {
+        DeliveryConfig {
+            rate_per_minute: 1,
+            burst: 1,
+            backlog_max,
+            backlog_policy,
+            max_attempts: 1,
+            retry_backoff: BackoffPolicy { initial_delay: Duration::from_millis(1), max_delay: Duration::from_millis(1), multiplier: 2.0, max_retries: None },
+            max_age: Some(Duration::from_secs(60)),
+            filter: SinkFilter { min_worth: Decimal::ZERO, symbols: SymbolFilter::new(&[], &[], Vec::new()).unwrap() },
+            time_display: TimeDisplay::new(chrono_tz::Tz::UTC, utils::DEFAULT_TIME_FORMAT).unwrap(),
+        }
+    }
+
+    fn notification(text: &str, worth: i64) -> Notification {
+        Notification { text: text.to_owned(), summary: text.to_owned(), worth: Decimal::from(worth), event_time: String::new(), details: None, delivery_id: String::new() }
+    }
+
+    fn backlog(worths: &[i64]) -> VecDeque<PendingNotification> {
+        worths.iter().enumerate().map(|(i, &worth)| PendingNotification::new(notification(&i.to_string(), worth), outbox::now_ms())).collect()
+    }
+
+    fn texts<'a>(notifications: impl Iterator<Item = &'a PendingNotification>) -> Vec<&'a str> {
+        notifications.map(|pending| pending.notification.text.as_str()).collect()
+    }
+
+    #[test]
+    fn dead_letter_policy_evicts_the_oldest_as_is() {
+        let mut backlog = backlog(&[5, 1, 3, 4]);
+        let mut skipped = None;
+        let stats = DeliveryStats::default();
+        let removed = enforce_backlog_bound(&mut backlog, &mut skipped, &config(2, BacklogPolicy::DeadLetter), &stats, &None, &RecordingSink::default());
+        assert_eq!(texts(removed.iter()), vec!["0", "1"]);
+        assert_eq!(texts(backlog.iter()), vec!["2", "3"]);
+        assert!(skipped.is_none());
+        assert_eq!(stats.dropped.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn summarize_policy_counts_the_smallest_into_summary() {
+        let mut backlog = backlog(&[5, 1, 3, 4]);
+        let mut skipped = None;
+        let stats = DeliveryStats::default();
+        let removed = enforce_backlog_bound(&mut backlog, &mut skipped, &config(2, BacklogPolicy::Summarize), &stats, &None, &RecordingSink::default());
+        assert_eq!(texts(removed.iter()), vec!["1", "2"]);
+        assert_eq!(texts(backlog.iter()), vec!["0", "3"]);
+        let skipped = skipped.unwrap();
+        assert_eq!((skipped.count, skipped.worth), (2, Decimal::from(4)));
+        assert_eq!(stats.dropped.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn lossless_sink_evicts_the_oldest_as_is_whatever_the_policy() {
+        let sink = RecordingSink { lossless: true, ..Default::default() };
+        for policy in [BacklogPolicy::Summarize, BacklogPolicy::Merge, BacklogPolicy::DropSmallest] {
+            let mut backlog = backlog(&[5, 1, 3, 4]);
+            let mut skipped = None;
+            let removed = enforce_backlog_bound(&mut backlog, &mut skipped, &config(2, policy), &DeliveryStats::default(), &None, &sink);
+            assert_eq!(texts(removed.iter()), vec!["0", "1"]);
+            assert_eq!(texts(backlog.iter()), vec!["2", "3"]);
+            assert!(skipped.is_none());
+        }
+    }
+
+    #[test]
+    fn merge_policy_merges_adjacent_within_message_length() {
+        let mut backlog = backlog(&[5, 1, 3]);
+        let mut skipped = None;
+        let removed = enforce_backlog_bound(&mut backlog, &mut skipped, &config(2, BacklogPolicy::Merge), &DeliveryStats::default(), &None, &RecordingSink::default());
+        assert!(removed.is_empty());
+        assert_eq!(texts(backlog.iter()), vec!["0\n1", "2"]);
+        assert_eq!(backlog[0].notification.worth, Decimal::from(6));
+    }
+
+    #[test]
+    fn summary_shows_exact_worth_rounded_to_3_decimal_places() {
+        let skipped = Skipped { count: 2, worth: Decimal::from_str("1234.56789").unwrap() };
+        let summary = summary_notification(&skipped);
+        assert_eq!(summary.text, "Bybit shiprekt 2 more positions (worth $1,234.568 in total) not shown individually due to rate limit");
+        assert_eq!(summary.worth, Decimal::from_str("1234.56789").unwrap());
+    }
+
+    #[test]
+    fn notification_with_malformed_details_fails_to_deserialize() {
+        let json_str = r#"{"text":"a","summary":"a","worth":"1","event_time":"","details":{"symbol":"BTCUSDT"}}"#;
+        assert!(serde_json::from_str::<Notification>(json_str).is_err());
+        let json_str = r#"{"text":"a","summary":"a","worth":"1","event_time":""}"#;
+        assert!(serde_json::from_str::<Notification>(json_str).unwrap().details.is_none());
+    }
+
+    /// Wait until the condition holds, or give up after a while.
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_notification_is_dead_lettered_without_taking_token() {
+        let sink = Arc::new(RecordingSink::default());
+        let replayed = vec![
+            OutboxEntry { id: 0, sink: "recording".to_owned(), created_at_ms: 1, notification: notification("stale", 1) },
+            OutboxEntry { id: 1, sink: "recording".to_owned(), created_at_ms: outbox::now_ms(), notification: notification("fresh", 1) },
+        ];
+        // burst of 1 at 1 per minute, so fresh one is only sent in time if
+        // stale one leaves the token to it
+        let handle = spawn_delivery(sink.clone(), 8, config(100, BacklogPolicy::DeadLetter), None, replayed);
+        wait_for(|| !sink.sent.lock().unwrap().is_empty()).await;
+
+        assert_eq!(*sink.sent.lock().unwrap(), vec!["fresh"]);
+        assert_eq!(*sink.dead_lettered.lock().unwrap(), vec![("stale".to_owned(), "stale".to_owned())]);
+        assert_eq!(handle.stats.stale.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_notification_is_dead_lettered_with_error() {
+        let sink = Arc::new(RecordingSink { error: Some(SinkError::Permanent("HTTP 400: bad".to_owned())), ..Default::default() });
+        let handle = spawn_delivery(sink.clone(), 8, config(100, BacklogPolicy::DeadLetter), None, Vec::new());
+        handle.enqueue(notification("a", 1)).unwrap();
+        wait_for(|| !sink.dead_lettered.lock().unwrap().is_empty()).await;
+
+        assert_eq!(*sink.dead_lettered.lock().unwrap(), vec![("a".to_owned(), "HTTP 400: bad (permanent)".to_owned())]);
+        assert_eq!(handle.stats.failed.load(Ordering::Relaxed), 1);
+    }
+}
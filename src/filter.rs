@@ -0,0 +1,207 @@
+use crate::types::{ContractCategory, OperationError};
+use crate::delivery::Notification;
+use crate::contract::ContractSpec;
+use crate::liquidation::Liquidation;
+
+use regex::Regex;
+use rust_decimal::Decimal;
+
+/// Filter of symbols by allow-list, deny-list, and contract categories.
+/// Entries of allow-list, and deny-list can be either exact symbol e.g.
+/// BTCUSDT, or glob pattern e.g. `*USDT`, `BTC*`.
+/// Deny-list takes precedence over allow-list, and empty allow-list, or
+/// empty categories mean allowing everything.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolFilter {
+    allow: Vec<SymbolPattern>,
+    deny: Vec<SymbolPattern>,
+    categories: Vec<ContractCategory>,
+}
+
+/// Either exact symbol, or glob pattern compiled into regex.
+#[derive(Debug, Clone)]
+enum SymbolPattern {
+    Exact(String),
+    Glob(Regex),
+}
+
+impl SymbolPattern {
+    /// Create a pattern from exact symbol, or glob pattern.
+    /// Only `*`, and `?` are supported as wildcard.
+    fn new(pattern: &str) -> Result<Self, OperationError> {
+        if !pattern.contains(['*', '?']) {
+            return Ok(SymbolPattern::Exact(pattern.to_owned()));
+        }
+
+        let regex_str = format!("^{}$", regex::escape(pattern).replace("\\*", ".*").replace("\\?", "."));
+        match Regex::new(&regex_str) {
+            Ok(regex) => Ok(SymbolPattern::Glob(regex)),
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "invalid symbol pattern '{}'; err={}", pattern, e),
+        }
+    }
+
+    fn is_match(&self, symbol: &str) -> bool {
+        match self {
+            SymbolPattern::Exact(s) => s == symbol,
+            SymbolPattern::Glob(regex) => regex.is_match(symbol),
+        }
+    }
+}
+
+impl SymbolFilter {
+    /// Create a new filter.
+    ///
+    /// # Arguments
+    /// * `allow` - symbols, or glob patterns to allow; empty to allow all
+    /// * `deny` - symbols, or glob patterns to deny
+    /// * `categories` - contract categories to allow; empty to allow all
+    pub fn new(allow: &[String], deny: &[String], categories: Vec<ContractCategory>) -> Result<Self, OperationError> {
+        Ok(Self {
+            allow: allow.iter().map(|p| SymbolPattern::new(p)).collect::<Result<_, _>>()?,
+            deny: deny.iter().map(|p| SymbolPattern::new(p)).collect::<Result<_, _>>()?,
+            categories,
+        })
+    }
+
+    /// Check whether the specified symbol passes the filter.
+    ///
+    /// # Arguments
+    /// * `symbol` - fully qualified symbol
+    pub fn matches(&self, symbol: &str) -> bool {
+        self.matches_in_category(symbol, ContractSpec::from_symbol(symbol).category)
+    }
+
+    /// Check whether the liquidation passes the filter by its symbol, and
+    /// kind of contract.
+    ///
+    /// # Arguments
+    /// * `liquidation` - liquidation to check
+    pub fn matches_liquidation(&self, liquidation: &Liquidation) -> bool {
+        self.matches_in_category(&liquidation.symbol, liquidation.kind.category())
+    }
+
+    fn matches_in_category(&self, symbol: &str, category: ContractCategory) -> bool {
+        if self.deny.iter().any(|p| p.is_match(symbol)) {
+            return false;
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|p| p.is_match(symbol)) {
+            return false;
+        }
+        self.categories.is_empty() || self.categories.contains(&category)
+    }
+
+    /// Create a copy of filter with allow-list replaced, keeping deny-list,
+    /// and categories.
+    ///
+    /// # Arguments
+    /// * `allow` - symbols, or glob patterns to allow; empty to allow all
+    pub fn with_allow(&self, allow: &[String]) -> Result<Self, OperationError> {
+        Ok(Self {
+            allow: allow.iter().map(|p| SymbolPattern::new(p)).collect::<Result<_, _>>()?,
+            deny: self.deny.clone(),
+            categories: self.categories.clone(),
+        })
+    }
+
+    /// Get exact symbols of allow-list i.e. entries which are not glob
+    /// pattern. These can be subscribed to directly.
+    pub fn exact_allowed_symbols(&self) -> Vec<String> {
+        self.allow.iter()
+            .filter_map(|p| match p {
+                SymbolPattern::Exact(s) => Some(s.clone()),
+                SymbolPattern::Glob(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Filter of notifications to be delivered to a single sink, on top of the
+/// global symbol filter.
+#[derive(Debug, Clone, Default)]
+pub struct SinkFilter {
+    /// Minimum worth in USD of liquidation to be delivered
+    pub min_worth: Decimal,
+
+    /// Filter of symbols
+    pub symbols: SymbolFilter,
+}
+
+impl SinkFilter {
+    /// Check whether the notification passes the filter.
+    /// Notification not of a single liquidation is only checked against its
+    /// worth.
+    ///
+    /// # Arguments
+    /// * `notification` - notification to check
+    pub fn matches(&self, notification: &Notification) -> bool {
+        if notification.worth < self.min_worth {
+            return false;
+        }
+        match &notification.details {
+            Some(liquidation) => self.symbols.matches_liquidation(liquidation),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    fn filter(allow: &[&str], deny: &[&str], categories: Vec<ContractCategory>) -> SymbolFilter {
+        let to_strings = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        SymbolFilter::new(&to_strings(allow), &to_strings(deny), categories).unwrap()
+    }
+
+    #[test]
+    fn glob_matches_whole_symbol() {
+        let filter = filter(&["*USDT", "BTC?SD"], &[], Vec::new());
+        assert!(filter.matches("BTCUSDT"));
+        assert!(filter.matches("1000PEPEUSDT"));
+        assert!(filter.matches("BTCUSD"));
+        assert!(!filter.matches("BTCUSDT-27DEC24"));
+        assert!(!filter.matches("ETHUSD"));
+        assert_eq!(filter.exact_allowed_symbols(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn deny_beats_allow() {
+        let filter = filter(&["BTC*", "ETHUSDT"], &["*PERP", "ETHUSDT"], Vec::new());
+        assert!(filter.matches("BTCUSDT"));
+        assert!(!filter.matches("BTCPERP"));
+        assert!(!filter.matches("ETHUSDT"));
+    }
+
+    #[test]
+    fn empty_allow_list_allows_all_but_denied() {
+        let filter = filter(&[], &["ETH*"], Vec::new());
+        assert!(filter.matches("BTCUSDT"));
+        assert!(filter.matches("XRPUSD"));
+        assert!(!filter.matches("ETHUSD"));
+    }
+
+    #[test]
+    fn category_is_of_contract_of_symbol() {
+        let filter = filter(&[], &[], vec![ContractCategory::InversePerpetual, ContractCategory::LinearFutures]);
+        assert!(filter.matches("BTCUSD"));
+        assert!(filter.matches("BTCUSDT-27DEC24"));
+        assert!(!filter.matches("BTCUSDT"));
+        assert!(!filter.matches("BTCUSDM22"));
+    }
+
+    #[test]
+    fn sink_filter_checks_min_worth_then_symbol() {
+        let sink_filter = SinkFilter { min_worth: Decimal::from(10_000), symbols: filter(&["BTC*"], &[], Vec::new()) };
+        // 32,000, and 6,400 USD
+        assert!(sink_filter.matches(&test_notification::liquidation("BTCUSDT", Side::Buy, "64000", "0.5")));
+        assert!(!sink_filter.matches(&test_notification::liquidation("BTCUSDT", Side::Buy, "64000", "0.1")));
+        assert!(!sink_filter.matches(&test_notification::liquidation("ETHUSDT", Side::Buy, "3200", "10")));
+
+        // notice has no symbol, only its worth is checked
+        let notice = test_notification::notice("summary");
+        assert!(!sink_filter.matches(&notice));
+        assert!(sink_filter.matches(&Notification { worth: Decimal::from(10_000), ..notice }));
+    }
+}
@@ -1,5 +1,10 @@
-use crate::types::OperationError;
+use crate::types::{OperationError, ProtocolVersion, SubscribeFailurePolicy, ContractCategory, ContractKind, Side, PositionSide, SettleCurrency, BacklogPolicy, SmtpSecurity, MqttVersion, TimeDisplay};
+use crate::sinks::{SinkError, SinkHealth};
+use chrono::TimeZone;
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
 use std::fmt::{Display, Formatter, Error};
+use std::str::FromStr;
 
 impl Display for OperationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
@@ -35,7 +40,300 @@ impl Display for OperationError {
                     Some(msg) => write!(f, "error in internal syncing-communication mechanism; {}", msg),
                     None => write!(f, "error in internal syncing-communication mechanism")
                 }
+            },
+            OptErr::ErrorOutboxIo(opt_msg) => {
+                match opt_msg {
+                    Some(msg) => write!(f, "error in I/O of outbox; {}", msg),
+                    None => write!(f, "error in I/O of outbox")
+                }
             }
         }
     }
 }
+
+impl Display for SinkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SinkError::RetryAfter(retry_after) => write!(f, "retry after {:?}", retry_after),
+            SinkError::Transient(msg) => write!(f, "{}", msg),
+            SinkError::Permanent(msg) => write!(f, "{} (permanent)", msg),
+        }
+    }
+}
+
+impl Display for SinkHealth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SinkHealth::Healthy => write!(f, "healthy"),
+            SinkHealth::Degraded(msg) => write!(f, "degraded ({})", msg),
+            SinkHealth::Unhealthy(msg) => write!(f, "unhealthy ({})", msg),
+        }
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "v2" => Ok(ProtocolVersion::V2),
+            "v5" => Ok(ProtocolVersion::V5),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown protocol version '{}', expected v2 or v5", s),
+        }
+    }
+}
+
+impl FromStr for SubscribeFailurePolicy {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "retry" => Ok(SubscribeFailurePolicy::Retry),
+            "abort" => Ok(SubscribeFailurePolicy::Abort),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown subscribe failure policy '{}', expected retry or abort", s),
+        }
+    }
+}
+
+impl Display for ContractCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ContractCategory::InversePerpetual => write!(f, "inverse_perpetual"),
+            ContractCategory::LinearPerpetual => write!(f, "linear_perpetual"),
+            ContractCategory::InverseFutures => write!(f, "inverse_futures"),
+            ContractCategory::LinearFutures => write!(f, "linear_futures"),
+        }
+    }
+}
+
+impl FromStr for ContractCategory {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "inverse_perpetual" => Ok(ContractCategory::InversePerpetual),
+            "linear_perpetual" => Ok(ContractCategory::LinearPerpetual),
+            "inverse_futures" => Ok(ContractCategory::InverseFutures),
+            "linear_futures" => Ok(ContractCategory::LinearFutures),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown contract category '{}', expected inverse_perpetual, linear_perpetual, inverse_futures, or linear_futures", s),
+        }
+    }
+}
+
+impl Side {
+    /// Get the side of position liquidated.
+    pub fn position_side(self) -> PositionSide {
+        match self {
+            Side::Buy => PositionSide::Long,
+            Side::Sell => PositionSide::Short,
+        }
+    }
+}
+
+impl Display for PositionSide {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            PositionSide::Long => write!(f, "Long"),
+            PositionSide::Short => write!(f, "Short"),
+        }
+    }
+}
+
+impl ContractKind {
+    /// Get the kind of contract of the category settled in the currency.
+    ///
+    /// # Arguments
+    /// * `category` - category of contract
+    /// * `settle_currency` - currency contract is settled in
+    pub fn new(category: ContractCategory, settle_currency: &SettleCurrency) -> Self {
+        match (category, settle_currency) {
+            (ContractCategory::InversePerpetual, _) => ContractKind::InversePerpetual,
+            (ContractCategory::InverseFutures, _) => ContractKind::InverseFutures,
+            (ContractCategory::LinearPerpetual, SettleCurrency::Usdc) => ContractKind::UsdcPerpetual,
+            (ContractCategory::LinearPerpetual, _) => ContractKind::LinearPerpetual,
+            (ContractCategory::LinearFutures, SettleCurrency::Usdc) => ContractKind::UsdcFutures,
+            (ContractCategory::LinearFutures, _) => ContractKind::LinearFutures,
+        }
+    }
+
+    /// Get the category of contract e.g. to be filtered by.
+    pub fn category(self) -> ContractCategory {
+        match self {
+            ContractKind::InversePerpetual => ContractCategory::InversePerpetual,
+            ContractKind::InverseFutures => ContractCategory::InverseFutures,
+            ContractKind::LinearPerpetual | ContractKind::UsdcPerpetual => ContractCategory::LinearPerpetual,
+            ContractKind::LinearFutures | ContractKind::UsdcFutures => ContractCategory::LinearFutures,
+        }
+    }
+
+    /// Get the type of contract to be shown to human e.g. `Perpetual futures`.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            ContractKind::InversePerpetual | ContractKind::LinearPerpetual | ContractKind::UsdcPerpetual => "Perpetual futures",
+            ContractKind::InverseFutures | ContractKind::LinearFutures | ContractKind::UsdcFutures => "Futures",
+        }
+    }
+}
+
+impl Display for ContractKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ContractKind::InversePerpetual => write!(f, "inverse_perpetual"),
+            ContractKind::InverseFutures => write!(f, "inverse_futures"),
+            ContractKind::LinearPerpetual => write!(f, "linear_perpetual"),
+            ContractKind::LinearFutures => write!(f, "linear_futures"),
+            ContractKind::UsdcPerpetual => write!(f, "usdc_perpetual"),
+            ContractKind::UsdcFutures => write!(f, "usdc_futures"),
+        }
+    }
+}
+
+impl Display for SettleCurrency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SettleCurrency::Usdt => write!(f, "USDT"),
+            SettleCurrency::Usdc => write!(f, "USDC"),
+            SettleCurrency::Coin(coin) => write!(f, "{}", coin),
+        }
+    }
+}
+
+impl From<&str> for SettleCurrency {
+    fn from(s: &str) -> Self {
+        match s {
+            "USDT" => SettleCurrency::Usdt,
+            "USDC" => SettleCurrency::Usdc,
+            coin => SettleCurrency::Coin(coin.to_owned()),
+        }
+    }
+}
+
+impl From<String> for SettleCurrency {
+    fn from(s: String) -> Self {
+        SettleCurrency::from(s.as_str())
+    }
+}
+
+impl From<SettleCurrency> for String {
+    fn from(settle_currency: SettleCurrency) -> Self {
+        settle_currency.to_string()
+    }
+}
+
+impl FromStr for BacklogPolicy {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "drop_smallest" => Ok(BacklogPolicy::DropSmallest),
+            "merge" => Ok(BacklogPolicy::Merge),
+            "summarize" => Ok(BacklogPolicy::Summarize),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown backlog policy '{}', expected drop_smallest, merge, or summarize", s),
+        }
+    }
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "plain" => Ok(SmtpSecurity::Plain),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown SMTP security '{}', expected starttls, tls, or plain", s),
+        }
+    }
+}
+
+impl FromStr for MqttVersion {
+    type Err = OperationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "3.1.1" | "v3" => Ok(MqttVersion::V311),
+            "5" | "v5" => Ok(MqttVersion::V5),
+            _ => ret_err!(OperationError::ErrorInternalGeneric, "unknown MQTT version '{}', expected 3.1.1, or 5", s),
+        }
+    }
+}
+
+impl TimeDisplay {
+    /// Create a new time display.
+    ///
+    /// # Arguments
+    /// * `timezone` - timezone to display in
+    /// * `format` - format as of chrono's strftime
+    ///
+    /// # Returns
+    /// `Err` with `OperationError::ErrorInternalGeneric` if format has
+    /// unknown specifier, which would otherwise fail every formatting.
+    pub fn new(timezone: Tz, format: &str) -> Result<Self, OperationError> {
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            ret_err!(OperationError::ErrorInternalGeneric, "invalid time format '{}'", format);
+        }
+        Ok(Self { timezone, format: format.to_owned() })
+    }
+
+    /// Format timestamp in milliseconds.
+    ///
+    /// # Arguments
+    /// * `ms` - timestamp in milliseconds
+    pub fn format_ms(&self, ms: u64) -> String {
+        match self.timezone.timestamp_millis_opt(ms as i64) {
+            chrono::LocalResult::Single(datetime) => datetime.format(&self.format).to_string(),
+            _ => ms.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contract_kind_of_each_category_and_settle_currency() {
+        let btc = SettleCurrency::Coin("BTC".to_owned());
+        let cases = [
+            (ContractCategory::InversePerpetual, btc.clone(), ContractKind::InversePerpetual),
+            (ContractCategory::InverseFutures, btc, ContractKind::InverseFutures),
+            (ContractCategory::LinearPerpetual, SettleCurrency::Usdt, ContractKind::LinearPerpetual),
+            (ContractCategory::LinearFutures, SettleCurrency::Usdt, ContractKind::LinearFutures),
+            (ContractCategory::LinearPerpetual, SettleCurrency::Usdc, ContractKind::UsdcPerpetual),
+            (ContractCategory::LinearFutures, SettleCurrency::Usdc, ContractKind::UsdcFutures),
+        ];
+        for (category, settle_currency, kind) in cases {
+            assert_eq!(ContractKind::new(category, &settle_currency), kind);
+            assert_eq!(kind.category(), category);
+        }
+    }
+
+    #[test]
+    fn contract_kind_displays_as_snake_case() {
+        assert_eq!(ContractKind::LinearFutures.to_string(), "linear_futures");
+        assert_eq!(ContractKind::UsdcPerpetual.to_string(), "usdc_perpetual");
+    }
+
+    #[test]
+    fn time_is_formatted_in_timezone_with_milliseconds() {
+        let utc = TimeDisplay::new(Tz::UTC, "%Y-%m-%d %H:%M:%S%.3f %Z").unwrap();
+        assert_eq!(utc.format_ms(1_700_000_000_748), "2023-11-14 22:13:20.748 UTC");
+        assert_eq!(utc.format_ms(1_700_000_000_005), "2023-11-14 22:13:20.005 UTC");
+
+        let bangkok = TimeDisplay::new(Tz::Asia__Bangkok, "%Y-%m-%d %H:%M:%S%.3f %Z").unwrap();
+        assert_eq!(bangkok.format_ms(1_700_000_000_748), "2023-11-15 05:13:20.748 +07");
+    }
+
+    #[test]
+    fn time_without_milliseconds_is_truncated_not_rounded() {
+        let display = TimeDisplay::new(Tz::UTC, "%H:%M:%S").unwrap();
+        assert_eq!(display.format_ms(1_700_000_000_999), "22:13:20");
+    }
+
+    #[test]
+    fn time_format_with_unknown_specifier_is_rejected() {
+        assert!(TimeDisplay::new(Tz::UTC, "%Y-%m-%d %Q").is_err());
+        assert!(TimeDisplay::new(Tz::UTC, "%Y-%m-%d %").is_err());
+        assert!(TimeDisplay::new(Tz::UTC, "%d %b %H:%M").is_ok());
+    }
+}
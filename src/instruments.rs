@@ -0,0 +1,430 @@
+use crate::types::{OperationError, ContractCategory};
+use crate::outbox;
+
+use isahc::{AsyncReadResponseExt, HttpClient};
+use isahc::config::Configurable;
+use tokio::sync::watch;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+/// Timeout of each request to instruments-info endpoint
+const INSTRUMENTS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum instruments per page of instruments-info endpoint
+const INSTRUMENTS_PAGE_LIMIT: u32 = 1000;
+
+/// Categories of instruments-info endpoint to load
+const INSTRUMENTS_CATEGORIES: [&str; 2] = ["linear", "inverse"];
+
+/// Registry used by the whole process
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// Instrument as of Bybit's v5 instruments-info endpoint; only fields needed
+/// are kept. It is also the format of cache file.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument {
+    /// Symbol e.g. BTCUSDT
+    pub symbol: String,
+
+    /// Type of contract e.g. `LinearPerpetual`, `InverseFutures`
+    pub contract_type: String,
+
+    /// Base coin e.g. BTC, or 1000PEPE
+    pub base_coin: String,
+
+    /// Quote coin e.g. USDT, USDC, or USD
+    pub quote_coin: String,
+
+    /// Settle coin e.g. USDT, or BTC for inverse
+    pub settle_coin: String,
+}
+
+impl Instrument {
+    /// Get the contract category of the instrument.
+    ///
+    /// # Returns
+    /// `None` if type of contract is not known e.g. newly added by Bybit.
+    pub fn category(&self) -> Option<ContractCategory> {
+        match self.contract_type.as_str() {
+            "LinearPerpetual" => Some(ContractCategory::LinearPerpetual),
+            "LinearFutures" => Some(ContractCategory::LinearFutures),
+            "InversePerpetual" => Some(ContractCategory::InversePerpetual),
+            "InverseFutures" => Some(ContractCategory::InverseFutures),
+            _ => None,
+        }
+    }
+}
+
+/// Response of instruments-info endpoint
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentsInfoResponse {
+    ret_code: i64,
+    ret_msg: String,
+    result: Option<InstrumentsInfoResult>,
+}
+
+/// Result of instruments-info endpoint; a page of instruments
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentsInfoResult {
+    list: Vec<Instrument>,
+
+    /// Cursor of the next page; empty if this is the last page
+    #[serde(default)]
+    next_page_cursor: String,
+}
+
+/// Cache file of instruments.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct InstrumentsCache {
+    /// Timestamp in milliseconds when instruments were fetched
+    fetched_at_ms: u64,
+
+    instruments: Vec<Instrument>,
+}
+
+/// Configuration of instrument registry.
+#[derive(Debug, Clone)]
+pub struct RegistryConfig {
+    /// Base URL of Bybit's REST API e.g. `https://api.bybit.com`; `None` not
+    /// to fetch, but only load from cache
+    pub base_url: Option<String>,
+
+    /// Path of cache file, if any
+    pub cache_path: Option<PathBuf>,
+
+    /// Interval to refresh instruments at; `None` not to refresh
+    pub refresh_interval: Option<Duration>,
+}
+
+/// Instruments by symbol as known so far.
+struct Registry {
+    instruments: RwLock<HashMap<String, Instrument>>,
+
+    /// Number of times instruments have been replaced
+    generation: watch::Sender<u64>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        Self { instruments: RwLock::new(HashMap::new()), generation: watch::channel(0).0 }
+    }
+
+    fn lookup(&self, symbol: &str) -> Option<Instrument> {
+        self.instruments.read().unwrap_or_else(|e| e.into_inner()).get(symbol).cloned()
+    }
+
+    fn symbols_in(&self, categories: &[ContractCategory]) -> Vec<String> {
+        let mut symbols: Vec<String> = self.instruments.read().unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|instrument| instrument.category().is_some_and(|category| categories.contains(&category)))
+            .map(|instrument| instrument.symbol.clone())
+            .collect();
+        symbols.sort();
+        symbols
+    }
+
+    /// Replace instruments.
+    fn replace(&self, instruments: Vec<Instrument>) {
+        let map = instruments.into_iter().map(|instrument| (instrument.symbol.clone(), instrument)).collect();
+        *self.instruments.write().unwrap_or_else(|e| e.into_inner()) = map;
+        self.generation.send_modify(|generation| *generation += 1);
+    }
+
+    /// Populate from cache file, then from instruments-info endpoint.
+    /// Failure of either is logged, and symbols not known are left to
+    /// heuristics.
+    async fn init(&self, config: &RegistryConfig) {
+        if let Some(cache_path) = &config.cache_path {
+            match load_cache(cache_path) {
+                Ok(Some(cache)) => {
+                    println!("[instruments] loaded {} instruments from cache {} fetched {}s ago", cache.instruments.len(), cache_path.display(), outbox::now_ms().saturating_sub(cache.fetched_at_ms) / 1000);
+                    self.replace(cache.instruments);
+                },
+                Ok(None) => (),
+                Err(e) => eprintln!("[instruments] {}", e),
+            }
+        }
+        if config.base_url.is_some() {
+            if let Err(e) = self.refresh(config).await {
+                eprintln!("[instruments] cannot load instruments, using cached ones, or heuristics; {}", e);
+            }
+        }
+    }
+
+    /// Fetch instruments, then replace those known so far, and write them
+    /// to cache file.
+    async fn refresh(&self, config: &RegistryConfig) -> Result<(), OperationError> {
+        let base_url = match &config.base_url {
+            Some(res) => res,
+            None => return Ok(()),
+        };
+        let instruments = fetch(base_url).await?;
+        let count = instruments.len();
+        if let Some(cache_path) = &config.cache_path {
+            if let Err(e) = save_cache(cache_path, &instruments) {
+                eprintln!("[instruments] {}", e);
+            }
+        }
+        self.replace(instruments);
+        println!("[instruments] loaded {} instruments from {}", count, base_url);
+        Ok(())
+    }
+}
+
+fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(Registry::new)
+}
+
+/// Watch for instruments of registry to be replaced e.g. by periodic
+/// refresh, after which symbols of newly listed instruments are known.
+pub fn changes() -> watch::Receiver<u64> {
+    registry().generation.subscribe()
+}
+
+/// Look up instrument of the symbol.
+///
+/// # Returns
+/// `None` if registry doesn't know the symbol e.g. it is not loaded.
+pub fn lookup(symbol: &str) -> Option<Instrument> {
+    registry().lookup(symbol)
+}
+
+/// Get symbols of instruments known so far in the specified categories.
+/// Instruments whose type of contract is not known are left out.
+///
+/// # Arguments
+/// * `categories` - categories of contract
+pub fn symbols_in(categories: &[ContractCategory]) -> Vec<String> {
+    registry().symbols_in(categories)
+}
+
+/// Populate registry from cache file, then from instruments-info endpoint.
+/// Failure of either is logged, and symbols not known are left to
+/// heuristics.
+///
+/// # Arguments
+/// * `config` - configuration
+pub async fn init(config: &RegistryConfig) {
+    registry().init(config).await
+}
+
+/// Refresh registry periodically as of configuration. It never returns if
+/// refresh is configured, so it should be spawned.
+///
+/// # Arguments
+/// * `config` - configuration
+pub async fn run_refresh(config: RegistryConfig) {
+    let interval = match config.refresh_interval {
+        Some(res) if config.base_url.is_some() => res,
+        _ => return,
+    };
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = registry().refresh(&config).await {
+            eprintln!("[instruments] cannot refresh instruments, keeping the previous ones; {}", e);
+        }
+    }
+}
+
+/// Fetch all instruments of all categories from instruments-info endpoint.
+///
+/// # Arguments
+/// * `base_url` - base URL of Bybit's REST API
+async fn fetch(base_url: &str) -> Result<Vec<Instrument>, OperationError> {
+    let client = match HttpClient::builder().timeout(INSTRUMENTS_REQUEST_TIMEOUT).build() {
+        Ok(res) => res,
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot create HTTP client; err={}", e),
+    };
+
+    let mut instruments = Vec::new();
+    for category in INSTRUMENTS_CATEGORIES {
+        let mut cursor = String::new();
+        loop {
+            let url = format!("{}/v5/market/instruments-info?category={}&limit={}&cursor={}",
+                base_url.trim_end_matches('/'), category, INSTRUMENTS_PAGE_LIMIT, url::form_urlencoded::byte_serialize(cursor.as_bytes()).collect::<String>());
+            let mut response = match client.get_async(url).await {
+                Ok(res) => res,
+                Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot request {} instruments; err={}", category, e),
+            };
+            if !response.status().is_success() {
+                ret_err!(OperationError::ErrorInternalGeneric, "cannot request {} instruments; status={}", category, response.status());
+            }
+            let body: InstrumentsInfoResponse = match response.json().await {
+                Ok(res) => res,
+                Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot parse {} instruments; err={}", category, e),
+            };
+            let result = match body.result {
+                Some(res) if body.ret_code == 0 => res,
+                _ => ret_err!(OperationError::ErrorInternalGeneric, "cannot get {} instruments; ret_code={}, ret_msg={}", category, body.ret_code, body.ret_msg),
+            };
+
+            instruments.extend(result.list);
+            if result.next_page_cursor.is_empty() {
+                break;
+            }
+            cursor = result.next_page_cursor;
+        }
+    }
+    Ok(instruments)
+}
+
+/// Load instruments from cache file.
+///
+/// # Returns
+/// `None` if there is no cache file.
+fn load_cache(path: &Path) -> Result<Option<InstrumentsCache>, OperationError> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(res) => res,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot read cache {}; err={}", path.display(), e),
+    };
+    let cache: InstrumentsCache = match serde_json::from_str(&content) {
+        Ok(res) => res,
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot parse cache {}; err={}", path.display(), e),
+    };
+    Ok(Some(cache))
+}
+
+/// Write instruments to cache file. It is written to a temporary file first,
+/// then renamed, so a crash in the middle doesn't leave a broken cache.
+fn save_cache(path: &Path, instruments: &[Instrument]) -> Result<(), OperationError> {
+    let cache = InstrumentsCache { fetched_at_ms: outbox::now_ms(), instruments: instruments.to_vec() };
+    let content = match serde_json::to_string(&cache) {
+        Ok(res) => res,
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot serialize cache; err={}", e),
+    };
+    let tmp_path = path.with_extension("tmp");
+    if let Err(e) = std::fs::write(&tmp_path, content) {
+        ret_err!(OperationError::ErrorInternalGeneric, "cannot write cache {}; err={}", tmp_path.display(), e);
+    }
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        ret_err!(OperationError::ErrorInternalGeneric, "cannot replace cache {}; err={}", path.display(), e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::http::test_server;
+
+    const LINEAR_PAGE_1: &str = r#"{"retCode":0,"retMsg":"OK","result":{"category":"linear","list":[
+        {"symbol":"BTCUSDT","contractType":"LinearPerpetual","status":"Trading","baseCoin":"BTC","quoteCoin":"USDT","settleCoin":"USDT"},
+        {"symbol":"1000PEPEUSDT","contractType":"LinearPerpetual","status":"Trading","baseCoin":"1000PEPE","quoteCoin":"USDT","settleCoin":"USDT"}
+    ],"nextPageCursor":"page 2"}}"#;
+
+    const LINEAR_PAGE_2: &str = r#"{"retCode":0,"retMsg":"OK","result":{"category":"linear","list":[
+        {"symbol":"BTCPERP","contractType":"LinearPerpetual","status":"Trading","baseCoin":"BTC","quoteCoin":"USDC","settleCoin":"USDC"},
+        {"symbol":"BTC-27DEC24","contractType":"LinearFutures","status":"Trading","baseCoin":"BTC","quoteCoin":"USDC","settleCoin":"USDC"}
+    ],"nextPageCursor":""}}"#;
+
+    const INVERSE_PAGE: &str = r#"{"retCode":0,"retMsg":"OK","result":{"category":"inverse","list":[
+        {"symbol":"BTCUSD","contractType":"InversePerpetual","status":"Trading","baseCoin":"BTC","quoteCoin":"USD","settleCoin":"BTC"},
+        {"symbol":"BTCUSDZ24","contractType":"InverseFutures","status":"Trading","baseCoin":"BTC","quoteCoin":"USD","settleCoin":"BTC"}
+    ],"nextPageCursor":""}}"#;
+
+    fn instrument(symbol: &str, contract_type: &str) -> Instrument {
+        Instrument {
+            symbol: symbol.to_owned(),
+            contract_type: contract_type.to_owned(),
+            base_coin: "BTC".to_owned(),
+            quote_coin: "USDT".to_owned(),
+            settle_coin: "USDT".to_owned(),
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bybit-shiprekt-instruments-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn symbols(instruments: &[Instrument]) -> Vec<&str> {
+        instruments.iter().map(|instrument| instrument.symbol.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn fetch_follows_next_page_cursor_of_each_category() {
+        let (url, server) = test_server::serve(vec![(200, vec![], LINEAR_PAGE_1), (200, vec![], LINEAR_PAGE_2), (200, vec![], INVERSE_PAGE)]).await;
+        let instruments = fetch(&url).await.unwrap();
+        assert_eq!(symbols(&instruments), vec!["BTCUSDT", "1000PEPEUSDT", "BTCPERP", "BTC-27DEC24", "BTCUSD", "BTCUSDZ24"]);
+
+        let received = server.await.unwrap();
+        assert!(received[0].head.starts_with("GET /v5/market/instruments-info?category=linear&limit=1000&cursor= "));
+        assert!(received[1].head.starts_with("GET /v5/market/instruments-info?category=linear&limit=1000&cursor=page+2 "));
+        assert!(received[2].head.starts_with("GET /v5/market/instruments-info?category=inverse&limit=1000&cursor= "));
+    }
+
+    #[tokio::test]
+    async fn fetch_fails_on_error_of_exchange() {
+        let (url, _server) = test_server::serve(vec![(200, vec![], r#"{"retCode":10001,"retMsg":"params error","result":null}"#)]).await;
+        assert!(fetch(&url).await.is_err());
+    }
+
+    #[test]
+    fn instruments_are_classified_by_contract_type() {
+        let registry = Registry::new();
+        registry.replace(vec![
+            instrument("BTCUSDT", "LinearPerpetual"),
+            instrument("BTC-27DEC24", "LinearFutures"),
+            instrument("BTCUSD", "InversePerpetual"),
+            instrument("BTCUSDZ24", "InverseFutures"),
+            instrument("BTC-27DEC24-70000-C", "LinearOption"),
+        ]);
+        assert_eq!(registry.symbols_in(&[ContractCategory::LinearPerpetual, ContractCategory::LinearFutures]), vec!["BTC-27DEC24", "BTCUSDT"]);
+        assert_eq!(registry.symbols_in(&[ContractCategory::InversePerpetual]), vec!["BTCUSD"]);
+        assert_eq!(registry.symbols_in(&[ContractCategory::InverseFutures]), vec!["BTCUSDZ24"]);
+        assert_eq!(registry.lookup("BTC-27DEC24-70000-C").and_then(|instrument| instrument.category()), None);
+        assert!(registry.lookup("ETHUSDT").is_none());
+    }
+
+    #[test]
+    fn cache_is_loaded_as_written() {
+        let path = temp_path("roundtrip");
+        assert!(load_cache(&path).unwrap().is_none());
+
+        save_cache(&path, &[instrument("BTCUSDT", "LinearPerpetual"), instrument("BTCUSD", "InversePerpetual")]).unwrap();
+        let cache = load_cache(&path).unwrap().unwrap();
+        assert_eq!(symbols(&cache.instruments), vec!["BTCUSDT", "BTCUSD"]);
+        assert_eq!(cache.instruments[1].contract_type, "InversePerpetual");
+        assert!(cache.fetched_at_ms > 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn cached_instruments_are_used_if_fetch_fails() {
+        let path = temp_path("fallback");
+        save_cache(&path, &[instrument("BTCUSDT", "LinearPerpetual")]).unwrap();
+        let cached = std::fs::read_to_string(&path).unwrap();
+        let (url, _server) = test_server::serve(vec![(503, vec![], "unavailable")]).await;
+
+        let registry = Registry::new();
+        registry.init(&RegistryConfig { base_url: Some(url), cache_path: Some(path.clone()), refresh_interval: None }).await;
+        assert_eq!(registry.symbols_in(&[ContractCategory::LinearPerpetual]), vec!["BTCUSDT"]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), cached);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn fetched_instruments_take_place_of_cached_ones() {
+        let path = temp_path("refresh");
+        save_cache(&path, &[instrument("ETHUSDT", "LinearPerpetual")]).unwrap();
+        let (url, _server) = test_server::serve(vec![(200, vec![], LINEAR_PAGE_1), (200, vec![], LINEAR_PAGE_2), (200, vec![], INVERSE_PAGE)]).await;
+
+        let registry = Registry::new();
+        let changes = registry.generation.subscribe();
+        registry.init(&RegistryConfig { base_url: Some(url), cache_path: Some(path.clone()), refresh_interval: None }).await;
+        assert!(registry.lookup("ETHUSDT").is_none());
+        assert_eq!(registry.symbols_in(&[ContractCategory::InverseFutures]), vec!["BTCUSDZ24"]);
+        assert_eq!(*changes.borrow(), 2);
+        assert_eq!(load_cache(&path).unwrap().unwrap().instruments.len(), 6);
+        let _ = std::fs::remove_file(&path);
+    }
+}
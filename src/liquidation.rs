@@ -0,0 +1,116 @@
+use crate::types::{BybitLiquidationData, Side, PositionSide, ContractKind, SettleCurrency};
+use crate::contract::ContractSpec;
+use crate::utils;
+
+use rust_decimal::Decimal;
+
+/// Liquidation normalized from data as received from websocket. It is formed
+/// once as it is received, then formatted, filtered, and delivered to all
+/// sinks as is.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Liquidation {
+    /// Symbol; ticker
+    pub symbol: String,
+
+    /// Side as notified by exchange
+    pub side: Side,
+
+    /// Kind of contract
+    pub kind: ContractKind,
+
+    /// Currency margin, and PnL are settled in
+    pub settle_currency: SettleCurrency,
+
+    /// Quantity
+    pub qty: Decimal,
+
+    /// Unit of quantity; USD contracts for inverse, base coin for linear
+    pub qty_currency: String,
+
+    /// Size in base coin
+    pub size: Decimal,
+
+    /// Base coin e.g. BTC
+    pub size_currency: String,
+
+    /// Bankruptcy price
+    pub price: Decimal,
+
+    /// Notional value in USD
+    pub worth: Decimal,
+
+    /// Timestamp in milliseconds when it happened as of exchange's clock
+    pub event_time_ms: u64,
+
+    /// Timestamp in milliseconds when it was received as of local clock
+    pub received_at_ms: u64,
+}
+
+impl Liquidation {
+    /// Normalize liquidation data as received from websocket.
+    ///
+    /// # Arguments
+    /// * `data` - liquidation data as received
+    /// * `received_at_ms` - timestamp in milliseconds when it was received
+    pub fn new(data: BybitLiquidationData, received_at_ms: u64) -> Self {
+        let spec = ContractSpec::from_symbol(&data.symbol);
+        let valuation = spec.value(data.price, data.qty);
+        Self {
+            kind: spec.kind(),
+            symbol: data.symbol,
+            side: data.side,
+            settle_currency: spec.settle_currency,
+            qty: data.qty,
+            qty_currency: spec.qty_unit,
+            size: valuation.size,
+            size_currency: spec.base_coin,
+            price: data.price,
+            worth: valuation.notional_usd,
+            event_time_ms: data.time,
+            received_at_ms,
+        }
+    }
+
+    /// Get the latency in milliseconds from the time it happened to the time
+    /// it was received. It can be negative if local clock is behind that of
+    /// exchange.
+    pub fn latency_ms(&self) -> i64 {
+        self.received_at_ms as i64 - self.event_time_ms as i64
+    }
+
+    /// Get the side of position liquidated.
+    pub fn position_side(&self) -> PositionSide {
+        self.side.position_side()
+    }
+
+    /// Format quantity along with its size in base coin if quantity is not
+    /// already in it e.g. `25,000 USD (0.39 BTC)`, or `1.5 BTC`.
+    pub fn quantity_string(&self) -> String {
+        let qty_str = format!("{} {}", utils::format_decimal(self.qty), self.qty_currency);
+        if self.size_currency.is_empty() || self.size_currency == self.qty_currency {
+            return qty_str;
+        }
+        format!("{} ({} {})", qty_str, utils::format_size(self.size), self.size_currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn liquidation(time: u64, received_at_ms: u64) -> Liquidation {
+        let data = BybitLiquidationData { symbol: "BTCUSDT".to_owned(), side: Side::Sell, price: Decimal::from(64000), qty: Decimal::from(1), time };
+        Liquidation::new(data, received_at_ms)
+    }
+
+    #[test]
+    fn latency_is_from_exchange_time_to_received_time() {
+        assert_eq!(liquidation(1_700_000_000_000, 1_700_000_000_250).latency_ms(), 250);
+        assert_eq!(liquidation(1_700_000_000_000, 1_700_000_000_000).latency_ms(), 0);
+    }
+
+    #[test]
+    fn latency_is_negative_if_local_clock_is_behind() {
+        assert_eq!(liquidation(1_700_000_000_250, 1_700_000_000_000).latency_ms(), -250);
+    }
+}
@@ -1,208 +1,402 @@
-use tungstenite::Message;
-use tungstenite::error::Error as TungsError;
-use futures_util::stream::StreamExt;
-use futures_util::sink::SinkExt;
-use rustelebot::*;
-use chrono::{NaiveDateTime, DateTime, Utc};
-use separator::Separatable;
-
-use std::time::Duration;
+use rust_decimal::Decimal;
+use tokio::sync::{mpsc, watch};
 
 #[macro_use] mod macros;
 mod types;
 mod deserialize;
 mod impls;
 mod utils;
+mod stream;
+mod protocol;
+mod v5;
+mod supervisor;
+mod watchdog;
+mod filter;
+mod contract;
+mod liquidation;
+mod instruments;
+mod delivery;
+mod ratelimit;
+mod outbox;
+mod sinks;
 
 use types::*;
+use supervisor::{BackoffPolicy, Supervisor, SupervisorHandle};
+use filter::{SymbolFilter, SinkFilter};
+use instruments::RegistryConfig;
+use liquidation::Liquidation;
+use delivery::{Notification, Dispatcher};
+use outbox::Outbox;
+use sinks::NotificationSink;
+use sinks::telegram::TelegramSink;
+use sinks::discord::DiscordSink;
+use sinks::slack::SlackSink;
+use sinks::matrix::MatrixSink;
+use sinks::webhook::WebhookSink;
+use sinks::smtp::SmtpDigestSink;
+use sinks::mqtt::MqttSink;
+use sinks::redis::RedisSink;
+use sinks::nats::NatsSink;
+use sinks::kafka::KafkaSink;
+use sinks::push::{NtfySink, GotifySink};
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Capacity of the shared liquidation pipeline fed by all endpoints
+const LIQUIDATION_PIPELINE_CAPACITY: usize = 1024;
+
+/// Capacity of queue of notifications waiting to be delivered to each sink
+const DELIVERY_QUEUE_CAPACITY: usize = 1024;
+
+/// Default path of durable outbox of notifications
+const DEFAULT_OUTBOX_PATH: &str = "bybit-shiprekt-outbox.jsonl";
+
+/// Default base URL of Bybit's REST API to load instruments from
+const DEFAULT_INSTRUMENTS_BASE_URL: &str = "https://api.bybit.com";
+
+/// Default path of cache of instruments
+const DEFAULT_INSTRUMENTS_CACHE_PATH: &str = "bybit-shiprekt-instruments.json";
+
+/// Default maximum number of notifications waiting to be sent to a sink
+/// consumed by machine before they are evicted to its dead-letter
+const MACHINE_BACKLOG_MAX: usize = 100_000;
+
+/// Interval of logging delivery stats
+const DELIVERY_STATS_LOG_INTERVAL: Duration = Duration::from_secs(300);
 
 #[tokio::main]
 async fn main() {
-    // create bot instance for telegram
-    let telegram_bot_instance = create_instance(
-        &match std::env::var("HX_BYBIT_SHIPREKT_TELEGRAM_BOT_TOKEN") {
-            Ok(res) => res,
-            Err(e) => errprint_exit1!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_TELEGRAM_BOT_TOKEN not defined; err={}", e),
+    // each sink is delivered to on its own task, so reading from websocket
+    // never waits for any of them
+    let mut sinks: Vec<(Arc<dyn NotificationSink>, DeliveryConfig)> = Vec::new();
+    if let Some(sink) = unwrap_or_exit1(TelegramSink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("TELEGRAM", 20, 3)));
+    }
+    if let Some(sink) = unwrap_or_exit1(DiscordSink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("DISCORD", 30, 5)));
+    }
+    if let Some(sink) = unwrap_or_exit1(SlackSink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("SLACK", 60, 3)));
+    }
+    if let Some(sink) = unwrap_or_exit1(MatrixSink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("MATRIX", 30, 5)));
+    }
+    if let Some(sink) = unwrap_or_exit1(WebhookSink::from_env()) {
+        sinks.push((Arc::new(sink), machine_delivery_config_from_env("WEBHOOK", 600, 20)));
+    }
+    if let Some(sink) = unwrap_or_exit1(MqttSink::from_env()) {
+        sinks.push((Arc::new(sink), machine_delivery_config_from_env("MQTT", 6000, 100)));
+    }
+    if let Some(sink) = unwrap_or_exit1(RedisSink::from_env()) {
+        sinks.push((Arc::new(sink), machine_delivery_config_from_env("REDIS", 6000, 100)));
+    }
+    if let Some(sink) = unwrap_or_exit1(NatsSink::from_env().await) {
+        sinks.push((Arc::new(sink), machine_delivery_config_from_env("NATS", 6000, 100)));
+    }
+    if let Some(sink) = unwrap_or_exit1(KafkaSink::from_env()) {
+        sinks.push((Arc::new(sink), machine_delivery_config_from_env("KAFKA", 6000, 100)));
+    }
+    for (sink, symbols) in unwrap_or_exit1(NtfySink::from_env()) {
+        let mut config = delivery_config_from_env("NTFY", 12, 30);
+        // symbols of topic take place of those allowed for all topics
+        if !symbols.is_empty() {
+            config.filter.symbols = unwrap_or_exit1(config.filter.symbols.with_allow(&symbols));
+        }
+        sinks.push((Arc::new(sink), config));
+    }
+    if let Some(sink) = unwrap_or_exit1(GotifySink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("GOTIFY", 60, 10)));
+    }
+    // digest only collects, sending is on its own schedule
+    if let Some(sink) = unwrap_or_exit1(SmtpDigestSink::from_env()) {
+        sinks.push((Arc::new(sink), delivery_config_from_env("SMTP", 60_000, 1000)));
+    }
+    if sinks.is_empty() {
+        errprint_exit1!(OperationError::ErrorMissingRequiredEnvVar, "no notification sink configured; define environment variables of at least one sink");
+    }
+    for (sink, _) in sinks.iter() {
+        println!("Delivering to sink {}", sink.name());
+    }
+    // symbols only some sinks are after e.g. of ntfy topic might not be among
+    // those subscribed to otherwise
+    let sink_symbols: Vec<String> = sinks.iter().flat_map(|(_, config)| config.filter.symbols.exact_allowed_symbols()).collect();
+
+    // empty path means no durable outbox
+    let outbox_path = std::env::var("HX_BYBIT_SHIPREKT_OUTBOX_PATH").unwrap_or_else(|_| DEFAULT_OUTBOX_PATH.to_owned());
+    let (outbox, replayed) = if outbox_path.is_empty() {
+        (None, Vec::new())
+    }
+    else {
+        let (outbox, replayed) = unwrap_or_exit1(Outbox::open(std::path::Path::new(&outbox_path), outbox_max_age()));
+        (Some(outbox), replayed)
+    };
+    let dispatcher = Dispatcher::spawn(sinks, DELIVERY_QUEUE_CAPACITY, outbox, replayed);
+    for handle in dispatcher.handles() {
+        tokio::spawn(delivery::log_stats(handle.clone(), DELIVERY_STATS_LOG_INTERVAL));
+    }
+
+    // symbols are classified by registry from here on
+    let registry_config = instruments_config_from_env();
+    instruments::init(&registry_config).await;
+    tokio::spawn(instruments::run_refresh(registry_config));
+
+    // v5 subscribes per symbol, so without symbols specified it subscribes to
+    // all instruments of registry
+    let mut protocol_version = unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_PROTOCOL_VERSION", ProtocolVersion::V5));
+    let (linear_symbols, inverse_symbols, symbols_of_registry) = match (std::env::var("HX_BYBIT_SHIPREKT_V5_LINEAR_SYMBOLS"), std::env::var("HX_BYBIT_SHIPREKT_V5_INVERSE_SYMBOLS")) {
+        (Err(_), Err(_)) => {
+            let (linear, inverse) = registry_symbols();
+            (linear, inverse, true)
         },
-        &match std::env::var("HX_BYBIT_SHIPREKT_TELEGRAM_CHANNEL_CHAT_ID") {
-            Ok(res) => res,
-            Err(e) => errprint_exit1!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_TELEGRAM_CHANNEL_CHAT_ID not defined; err={}", e),
+        (linear, inverse) => (
+            utils::parse_symbol_list(&linear.unwrap_or_default()),
+            utils::parse_symbol_list(&inverse.unwrap_or_default()),
+            false),
+    };
+    if protocol_version == ProtocolVersion::V5 && linear_symbols.is_empty() && inverse_symbols.is_empty() {
+        eprintln!("No symbols to subscribe to via v5 as instrument registry is empty, falling back to v2");
+        protocol_version = ProtocolVersion::V2;
+    }
+    println!("Using protocol {:?}", protocol_version);
+
+    let symbol_filter = unwrap_or_exit1(SymbolFilter::new(
+        &utils::parse_symbol_list(&std::env::var("HX_BYBIT_SHIPREKT_SYMBOLS_ALLOW").unwrap_or_default()),
+        &utils::parse_symbol_list(&std::env::var("HX_BYBIT_SHIPREKT_SYMBOLS_DENY").unwrap_or_default()),
+        unwrap_or_exit1(utils::parse_category_list(&std::env::var("HX_BYBIT_SHIPREKT_CATEGORIES").unwrap_or_default()))));
+
+    let backoff_policy = BackoffPolicy {
+        initial_delay: Duration::from_millis(unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_BACKOFF_INITIAL_MS", 1000))),
+        max_delay: Duration::from_millis(unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_BACKOFF_MAX_MS", 60_000))),
+        multiplier: 2.0,
+        // 0 means retry forever
+        max_retries: match unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_BACKOFF_MAX_RETRIES", 0_u32)) {
+            0 => None,
+            n => Some(n),
+        },
+    };
+
+    let stream_config = StreamConfig {
+        heartbeat_interval: Duration::from_secs(unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_HEARTBEAT_INTERVAL_SECS", 30))),
+        liveness_timeout: Duration::from_secs(unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_LIVENESS_TIMEOUT_SECS", 90))),
+        subscribe_timeout: Duration::from_secs(unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_SUBSCRIBE_TIMEOUT_SECS", 10))),
+        subscribe_failure_policy: unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_SUBSCRIBE_FAILURE_POLICY", SubscribeFailurePolicy::Abort)),
+    };
+
+    // all endpoints feed into the same pipeline, so every market ends up on
+    // the same sinks
+    let (liquidation_tx, mut liquidation_rx) = mpsc::channel::<Liquidation>(LIQUIDATION_PIPELINE_CAPACITY);
+
+    let mut topics_txs = Vec::new();
+    for endpoint in protocol::endpoints(protocol_version, &linear_symbols, &inverse_symbols, &sink_symbols, &symbol_filter) {
+        let tx = liquidation_tx.clone();
+        let (topics_tx, topics_rx) = watch::channel(endpoint.topics.clone());
+        topics_txs.push(topics_tx);
+        let (supervisor, handle) = Supervisor::new(backoff_policy.clone());
+        let config = stream_config.clone();
+        match endpoint.protocol {
+            ProtocolVersion::V2 => println!("[{}] subscribing to all symbols", endpoint.name),
+            ProtocolVersion::V5 => println!("[{}] subscribing to {} symbols", endpoint.name, endpoint.topics.len()),
+        }
+        tokio::spawn(log_connection_state(endpoint.name, handle));
+        tokio::spawn(async move {
+            let name = endpoint.name;
+            if let Err(e) = stream::run_endpoint(endpoint, topics_rx, tx, supervisor, config).await {
+                eprintln!("[{}] {}", name, e);
+            }
         });
+    }
+    // only spawned tasks hold the sending end from now on
+    drop(liquidation_tx);
+    // newly listed instruments are subscribed to once registry is refreshed
+    if protocol_version == ProtocolVersion::V5 && symbols_of_registry {
+        tokio::spawn(track_registry_topics(topics_txs, sink_symbols, symbol_filter.clone()));
+    }
 
-    'main_reconnect_loop: loop {
-        println!("Connecting to ByBit websocket...");
-        // connect to wss
-        let (ws_stream, _response) = match utils::connect_async_to_wss("wss://stream.bybit.com/realtime").await {
-            Ok(res) => res,
-            Err(e) => errprint_exit1!(e),
-        };
-        println!("(connected)");
-
-        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
-        let mut heartbeat_interval = tokio::time::interval(Duration::from_secs(30));
-
-        // TODO: provide filtering options through cli e.g. BTCUSD, XRPUSD, etc
-        match ws_sender.send(Message::Text(r#"{"op": "subscribe", "args": ["liquidation"]}"#.into())).await {
-            Ok(_) => println!("subscribed to liquidation topic"),
-            Err(e) => errprint_exit1!(OperationError::ErrorWssTopicSubscription, "error subscribing to liquidation topic; err={}", e),
+    while let Some(liquidation) = liquidation_rx.recv().await {
+        // still needed even with per-symbol subscription as v2 subscribes to
+        // all symbols at once
+        if !symbol_filter.matches_liquidation(&liquidation) {
+            continue;
         }
+        dispatcher.dispatch(|time_display| format_liquidation(&liquidation, time_display));
+    }
 
-        loop {
-            tokio::select! {
-                msg_item = ws_receiver.next() => {
-                    match msg_item {
-                        Some(msg) => {
-                            match msg {
-                                Ok(Message::Text(json_str)) => {
-                                    match serde_json::from_str::<'_, VariantResponse>(&json_str) {
-                                        Ok(VariantResponse::Response(json_obj)) => {
-                                            // TODO: provide option flag at CLI to avoid printing the
-                                            // following. Fixed set to false for now.
-                                            if false {
-                                                // check 'op' field to differentiate type
-                                                // of response
-                                                match json_obj.request.op.to_lowercase().as_str() {
-                                                    "ping" => println!("recieved pong msg"),
-                                                    "subscribe" => println!("received subscribe msg"),
-                                                    _ => (),
-                                                }
-                                            }
-                                        },
-                                        Ok(VariantResponse::Liquidation(json_obj)) => {
-                                            let inner_json_obj = match json_obj.data {
-                                                GenericData::Liquidation(json_obj) => json_obj,
-                                            };
-
-                                            let base_currency = utils::get_base_currency(&inner_json_obj.symbol).unwrap_or("UNKNOWN");
-                                            let is_linear = utils::is_linear_perpetual(&inner_json_obj.symbol);
-                                            let side = if inner_json_obj.side == "Buy" { "Long" } else { "Short" };
-
-                                            let (ms, ns) = utils::get_ms_and_ns_pair(inner_json_obj.time);
-                                            // FIXME: dang, NaiveDateTime::from_timestamp requires i64, this means
-                                            // timestamp supports for 132 years further until 2102 since epoch 1970
-                                            let datetime: DateTime<Utc> = DateTime::from_utc(NaiveDateTime::from_timestamp(ms as i64, ns), Utc);
-                                            let bankruptcy_worth_str = ((inner_json_obj.price * inner_json_obj.qty as f64 * 1000.0_f64).round() / 1000.0_f64).separated_string();
-                                            let qty_str = inner_json_obj.qty.separated_string();
-                                            let price_str = inner_json_obj.price.separated_string();
-                                            let base_or_quote_currency_str = if is_linear { "USDT" } else { base_currency };
-
-                                            let message = format!("Bybit shiprekt a {side} position of {qty} {base_or_quote_currency} (worth ${bankruptcy_value}) on the {symbol} {perpetual_or_not} contract at ${price} - {datetime_str}",
-                                                side=side,
-                                                qty=qty_str,
-                                                base_or_quote_currency=base_or_quote_currency_str,
-                                                bankruptcy_value=bankruptcy_worth_str,
-                                                symbol=inner_json_obj.symbol,
-                                                perpetual_or_not=if utils::is_non_perpetual_contract(&inner_json_obj.symbol) { "Futures" } else { "Perpetual futures" },
-                                                price=price_str,
-                                                datetime_str=datetime.to_string());
-
-                                            match send_message(&telegram_bot_instance, &message) {
-                                                Ok(_) => println!("Notified event: {side} position of {symbol} worth ${bankruptcy_value} with {qty} {base_or_quote_currency} at ${price}",
-                                                                  symbol=inner_json_obj.symbol,
-                                                                  side=side,
-                                                                  bankruptcy_value=bankruptcy_worth_str,
-                                                                  qty=qty_str,
-                                                                  base_or_quote_currency=base_or_quote_currency_str,
-                                                                  price=price_str),
-                                                // FIXME: upstream fix for rustelebot for `Display` of `ErrorResult`
-                                                Err(e) => eprintln!("{}", e.msg)
-                                            }
-                                        },
-                                        Err(e) => eprintln!("-- error parsing JSON response: {} --", e),
-                                    }
-                                },
-                                Ok(Message::Ping(msg)) => println!("Received ping message; msg={:#?}", msg),
-                                Ok(Message::Pong(msg)) => println!("Received pong message; msg={:#?}", msg),
-                                Ok(Message::Binary(bins)) => println!("Received Binbary message, content={}", std::str::from_utf8(&bins).unwrap_or("unknown")),
-                                Ok(Message::Frame(frame)) => println!("Received Frame message, content={:?}", frame),
-                                Ok(Message::Close(optional_cf)) => match optional_cf {
-                                   _ => {
-                                       println!("(websocket closed)");
-                                       continue 'main_reconnect_loop;      // reconnect to websocket again
-                                   }
-                                },
-
-                                // from now they are error cases that we need to
-                                // reconnect to websocket if occur
-                                //
-                                // by break into the outer loop
-                                Err(TungsError::ConnectionClosed) => {
-                                    eprintln!("Error: connection closed");
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::AlreadyClosed) => {
-                                    eprintln!("Error: already closed");
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Io(e)) => {
-                                    eprintln!("Error: IO; err={}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Tls(e)) => {
-                                    eprintln!("Error:: Tls error; err={}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Capacity(e)) => {
-                                    type CError = tungstenite::error::CapacityError;
-                                    match e {
-                                        CError::TooManyHeaders => eprintln!("Error: CapacityError, too many headers"),
-                                        CError::MessageTooLong{ size, max_size } => eprintln!("Error: CapacityError, message too long with size={}, max_size={}", size, max_size),
-                                    }
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Protocol(e)) => {
-                                    eprintln!("Error: Protocol, err={}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::SendQueueFull(e)) => {
-                                    type PMsg = tungstenite::protocol::Message;
-
-                                    match e {
-                                        PMsg::Text(text) => eprintln!("Error: SendQueueFull for Text message, content={}", text),
-                                        PMsg::Binary(bins) => eprintln!("Error: SendQueueFull for Binary message, content={}", std::str::from_utf8(&bins).unwrap_or("unknown")),
-                                        PMsg::Ping(bins) => eprintln!("Error: SendQueueFull for Ping message, content={}", std::str::from_utf8(&bins).unwrap_or("unknown")),
-                                        PMsg::Pong(bins) => eprintln!("Error: SendQueueFull for Pong message, content={}", std::str::from_utf8(&bins).unwrap_or("unknown")),
-                                        PMsg::Close(close_frame_optional) => {
-                                            match close_frame_optional {
-                                                Some(close_frame) => eprintln!("Error: SendQueueFull for Close message, content={:?}", close_frame),
-                                                None => eprintln!("Error: SendQueueFull for Close message, no close-frame content")
-                                            }
-                                        },
-                                        PMsg::Frame(frame) => eprintln!("Error: SendQueueFull for Frame messasge, content={:?}", frame)
-                                    }
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Utf8) => {
-                                    eprintln!("Error: Utf8 coding error");
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Url(e)) => {
-                                    eprintln!("Error: Invalid Url; err={:?}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::Http(e)) => {
-                                    eprintln!("Error: Http error; err={:?}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                                Err(TungsError::HttpFormat(e)) => {
-                                    eprintln!("Error: Http format error; err{:?}", e);
-                                    continue 'main_reconnect_loop;
-                                },
-                            }
-                        },
-                        None => (),
-                    }
-                }
-                // NOTE: even heartbeat won't save us from arbitrary connection
-                // closing down (around ~12-14 hours of long running process from
-                // testing).
-                _ = heartbeat_interval.tick() => {
-                    match ws_sender.send(Message::Text(r#"{"op":"ping"}"#.into())).await {
-                        Ok(_) => println!("send ping message"),
-                        Err(e) => eprintln!("error sending ping message; err={}", e),
-                    }
+    errprint_exit1!(OperationError::ErrorWssConnect, "all endpoints stopped");
+}
+
+/// Get symbols of instruments of registry to subscribe to via v5.
+///
+/// # Returns
+/// Linear, and inverse symbols.
+fn registry_symbols() -> (Vec<String>, Vec<String>) {
+    (instruments::symbols_in(&[ContractCategory::LinearPerpetual, ContractCategory::LinearFutures]),
+     instruments::symbols_in(&[ContractCategory::InversePerpetual, ContractCategory::InverseFutures]))
+}
+
+/// Update topics of v5 endpoints whenever instruments of registry are
+/// replaced, so endpoints subscribe to those of newly listed instruments.
+///
+/// # Arguments
+/// * `topics_txs` - latest topics of each endpoint in order of `protocol::endpoints`
+/// * `sink_symbols` - exact symbols allowed by filters of sinks
+/// * `filter` - symbol filter
+async fn track_registry_topics(topics_txs: Vec<watch::Sender<Vec<String>>>, sink_symbols: Vec<String>, filter: SymbolFilter) {
+    let mut changes = instruments::changes();
+    while changes.changed().await.is_ok() {
+        let (linear_symbols, inverse_symbols) = registry_symbols();
+        for (endpoint, topics_tx) in protocol::endpoints(ProtocolVersion::V5, &linear_symbols, &inverse_symbols, &sink_symbols, &filter).into_iter().zip(topics_txs.iter()) {
+            topics_tx.send_if_modified(|topics| {
+                if *topics == endpoint.topics {
+                    return false;
                 }
-            }
+                *topics = endpoint.topics;
+                true
+            });
         }
     }
 }
+
+/// Unwrap the result or print the error then exit with error code 1.
+fn unwrap_or_exit1<T>(res: Result<T, OperationError>) -> T {
+    match res {
+        Ok(res) => res,
+        Err(e) => errprint_exit1!(e),
+    }
+}
+
+/// Form configuration of delivery of a sink from environment variables.
+/// Rate limit, and filter are specific to each sink, maximum attempts, and
+/// time display can be overridden for each sink, the rest is shared by all
+/// sinks.
+///
+/// # Arguments
+/// * `sink_env_name` - name of sink as used in its environment variables e.g. `TELEGRAM`
+/// * `default_rate_per_minute` - default messages allowed per minute
+/// * `default_burst` - default messages allowed in a burst
+fn delivery_config_from_env(sink_env_name: &str, default_rate_per_minute: u32, default_burst: u32) -> DeliveryConfig {
+    DeliveryConfig {
+        rate_per_minute: unwrap_or_exit1(utils::get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_RATE_PER_MINUTE", sink_env_name), default_rate_per_minute)),
+        burst: unwrap_or_exit1(utils::get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_BURST", sink_env_name), default_burst)),
+        backlog_max: unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_BACKLOG_MAX", 100)),
+        backlog_policy: unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_BACKLOG_POLICY", BacklogPolicy::Summarize)),
+        max_attempts: unwrap_or_exit1(utils::get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_MAX_ATTEMPTS", sink_env_name),
+                                                            unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_DELIVERY_MAX_ATTEMPTS", 5)))),
+        retry_backoff: BackoffPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            max_retries: None,
+        },
+        max_age: outbox_max_age(),
+        filter: SinkFilter {
+            min_worth: unwrap_or_exit1(utils::get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_MIN_WORTH", sink_env_name), Decimal::ZERO)),
+            symbols: unwrap_or_exit1(SymbolFilter::new(
+                &utils::parse_symbol_list(&std::env::var(format!("HX_BYBIT_SHIPREKT_{}_SYMBOLS_ALLOW", sink_env_name)).unwrap_or_default()),
+                &utils::parse_symbol_list(&std::env::var(format!("HX_BYBIT_SHIPREKT_{}_SYMBOLS_DENY", sink_env_name)).unwrap_or_default()),
+                Vec::new())),
+        },
+        time_display: unwrap_or_exit1(utils::time_display_from_env(sink_env_name)),
+    }
+}
+
+/// Form configuration of delivery of a sink consumed by machine e.g. webhook
+/// from environment variables. It is as of `delivery_config_from_env` except
+/// that backlog is bounded by its own `HX_BYBIT_SHIPREKT_<SINK>_BACKLOG_MAX`,
+/// as it is never summarized, nor merged, for the sink is lossless.
+///
+/// # Arguments
+/// * `sink_env_name` - name of sink as used in its environment variables e.g. `WEBHOOK`
+/// * `default_rate_per_minute` - default messages allowed per minute
+/// * `default_burst` - default messages allowed in a burst
+fn machine_delivery_config_from_env(sink_env_name: &str, default_rate_per_minute: u32, default_burst: u32) -> DeliveryConfig {
+    DeliveryConfig {
+        backlog_max: unwrap_or_exit1(utils::get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_BACKLOG_MAX", sink_env_name), MACHINE_BACKLOG_MAX)),
+        ..delivery_config_from_env(sink_env_name, default_rate_per_minute, default_burst)
+    }
+}
+
+/// Form configuration of instrument registry from environment variables.
+fn instruments_config_from_env() -> RegistryConfig {
+    // empty base URL means not to fetch, and empty path means no cache
+    let base_url = std::env::var("HX_BYBIT_SHIPREKT_INSTRUMENTS_BASE_URL").unwrap_or_else(|_| DEFAULT_INSTRUMENTS_BASE_URL.to_owned());
+    let cache_path = std::env::var("HX_BYBIT_SHIPREKT_INSTRUMENTS_CACHE_PATH").unwrap_or_else(|_| DEFAULT_INSTRUMENTS_CACHE_PATH.to_owned());
+    RegistryConfig {
+        base_url: if base_url.is_empty() { None } else { Some(base_url) },
+        cache_path: if cache_path.is_empty() { None } else { Some(std::path::PathBuf::from(cache_path)) },
+        // 0 means no refresh
+        refresh_interval: match unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_INSTRUMENTS_REFRESH_SECS", 3600_u64)) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+    }
+}
+
+/// Get the maximum age of notification to be sent from environment variable.
+fn outbox_max_age() -> Option<Duration> {
+    // 0 means no limit
+    match unwrap_or_exit1(utils::get_env_var_or("HX_BYBIT_SHIPREKT_OUTBOX_MAX_AGE_SECS", 3600_u64)) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Log connection state changes of the endpoint as observed via its
+/// supervisor's handle.
+///
+/// # Arguments
+/// * `name` - name of endpoint
+/// * `handle` - handle of supervisor of such endpoint
+async fn log_connection_state(name: &'static str, mut handle: SupervisorHandle) {
+    while handle.state_rx.changed().await.is_ok() {
+        let state = handle.state_rx.borrow().clone();
+        println!("[{}] state: {:?} (connects={}, connect_failures={}, reconnects={}, liveness_timeouts={}, last_rtt={}ms)",
+                 name,
+                 state,
+                 handle.stats.connects.load(Ordering::Relaxed),
+                 handle.stats.connect_failures.load(Ordering::Relaxed),
+                 handle.stats.reconnects.load(Ordering::Relaxed),
+                 handle.stats.liveness_timeouts.load(Ordering::Relaxed),
+                 handle.stats.last_rtt_ms.load(Ordering::Relaxed));
+    }
+}
+
+/// Form the notification of liquidation.
+///
+/// # Arguments
+/// * `liquidation` - liquidation as received from websocket
+/// * `time_display` - how time of liquidation is displayed
+fn format_liquidation(liquidation: &Liquidation, time_display: &TimeDisplay) -> Notification {
+    let event_time_str = time_display.format_ms(liquidation.event_time_ms);
+    let side = liquidation.position_side();
+    let bankruptcy_worth_str = utils::format_worth(liquidation.worth);
+    let price_str = utils::format_decimal(liquidation.price);
+    let contract_str = liquidation.kind.type_name();
+    let quantity_str = liquidation.quantity_string();
+
+    let text = format!("Bybit shiprekt a {side} position of {quantity} (worth ${bankruptcy_value}) on the {symbol} {perpetual_or_not} contract at ${price} - {datetime_str}",
+        side=side,
+        quantity=quantity_str,
+        bankruptcy_value=bankruptcy_worth_str,
+        symbol=liquidation.symbol,
+        perpetual_or_not=contract_str,
+        price=price_str,
+        datetime_str=event_time_str);
+
+    let summary = format!("{side} position of {symbol} worth ${bankruptcy_value} with {quantity} at ${price}",
+        symbol=liquidation.symbol,
+        side=side,
+        bankruptcy_value=bankruptcy_worth_str,
+        quantity=quantity_str,
+        price=price_str);
+
+    Notification {
+        text,
+        summary,
+        worth: liquidation.worth,
+        event_time: event_time_str,
+        details: Some(liquidation.clone()),
+        delivery_id: String::new(),
+    }
+}
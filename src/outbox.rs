@@ -0,0 +1,406 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of records written before the log is considered for compaction
+const COMPACTION_MIN_RECORDS: usize = 1000;
+
+/// Maximum number of records written with a single sync to disk
+const WRITER_MAX_BATCH: usize = 256;
+
+/// Record of append-only outbox log, one JSON object per line.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum OutboxRecord {
+    /// Notification is persisted before sending
+    Add {
+        id: u64,
+        /// Name of sink notification is to be sent to
+        sink: String,
+        created_at_ms: u64,
+        notification: Box<Notification>,
+    },
+
+    /// Notification is sent successfully
+    Ack { id: u64 },
+
+    /// Notification is given up; failed permanently, dropped, or stale
+    Discard { id: u64 },
+}
+
+/// Notification persisted in outbox.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    /// Id of entry in outbox
+    pub id: u64,
+
+    /// Name of sink notification is to be sent to
+    pub sink: String,
+
+    /// Timestamp in milliseconds when notification was created
+    pub created_at_ms: u64,
+
+    /// Notification
+    pub notification: Notification,
+}
+
+impl OutboxEntry {
+    fn to_record(&self) -> OutboxRecord {
+        OutboxRecord::Add { id: self.id, sink: self.sink.clone(), created_at_ms: self.created_at_ms, notification: Box::new(self.notification.clone()) }
+    }
+}
+
+/// Durable outbox of notifications as an append-only log on disk.
+/// Notifications are added before sending, and acknowledged (or discarded)
+/// after, so anything left un-acknowledged is replayed on the next start.
+/// Once opened, it is written by its own writer thread through
+/// `OutboxHandle`. Sending doesn't wait for the record to be synced to disk,
+/// so notification added within a moment before a crash (of the batch being
+/// written) may be lost, even though it may have been sent already.
+pub struct Outbox {
+    path: PathBuf,
+    file: File,
+    next_id: u64,
+    pending: BTreeMap<u64, OutboxEntry>,
+    records: usize,
+}
+
+impl Outbox {
+    /// Open outbox at the specified path, creating it if not exist.
+    /// Entries older than `max_age` are discarded, then log is compacted.
+    ///
+    /// # Arguments
+    /// * `path` - path to outbox log file
+    /// * `max_age` - maximum age of entry to be replayed, `None` for no limit
+    ///
+    /// # Returns
+    /// Outbox along with the un-acknowledged entries to be sent again in order.
+    pub fn open(path: &Path, max_age: Option<Duration>) -> Result<(Self, Vec<OutboxEntry>), OperationError> {
+        let mut pending = BTreeMap::new();
+        let mut next_id = 0;
+
+        if path.exists() {
+            let file = match File::open(path) {
+                Ok(res) => res,
+                Err(e) => ret_err!(OperationError::ErrorOutboxIo, "cannot open {}; err={}", path.display(), e),
+            };
+            for (line_no, line) in BufReader::new(file).lines().enumerate() {
+                let line = match line {
+                    Ok(res) => res,
+                    Err(e) => ret_err!(OperationError::ErrorOutboxIo, "cannot read {}; err={}", path.display(), e),
+                };
+                // last line might be partially written if the process died
+                // while writing, skip it rather than refusing to start
+                let record = match serde_json::from_str::<OutboxRecord>(&line) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        eprintln!("Skipped corrupted outbox record at line {}; err={}", line_no + 1, e);
+                        continue;
+                    },
+                };
+                match record {
+                    OutboxRecord::Add { id, sink, created_at_ms, notification } => {
+                        next_id = next_id.max(id + 1);
+                        pending.insert(id, OutboxEntry { id, sink, created_at_ms, notification: *notification });
+                    },
+                    OutboxRecord::Ack { id } | OutboxRecord::Discard { id } => {
+                        pending.remove(&id);
+                    },
+                }
+            }
+        }
+
+        let before = pending.len();
+        pending.retain(|_, entry| !is_stale(entry.created_at_ms, max_age));
+        if before > pending.len() {
+            println!("Discarded {} stale notification(s) from outbox", before - pending.len());
+        }
+
+        let file = open_append(path)?;
+        let mut outbox = Self { path: path.to_owned(), file, next_id, pending, records: 0 };
+        outbox.compact()?;
+
+        let entries = outbox.pending.values().cloned().collect();
+        Ok((outbox, entries))
+    }
+
+    /// Spawn the writer thread writing records sent through the returned
+    /// handle.
+    pub fn spawn_writer(self) -> OutboxHandle {
+        let (tx, rx) = mpsc::channel::<OutboxRecord>();
+        let next_id = Arc::new(AtomicU64::new(self.next_id));
+        std::thread::spawn(move || self.run_writer(rx));
+        OutboxHandle { tx, next_id }
+    }
+
+    /// Write records as they come until all handles are gone. Records
+    /// arriving while the previous batch is written are written together as
+    /// the next batch with a single sync to disk.
+    fn run_writer(mut self, rx: Receiver<OutboxRecord>) {
+        while let Ok(record) = rx.recv() {
+            let mut batch = vec![record];
+            batch.extend(rx.try_iter().take(WRITER_MAX_BATCH - 1));
+            if let Err(e) = self.write_batch(batch) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    /// Append records to the log, then sync it once.
+    ///
+    /// # Arguments
+    /// * `records` - records to write in order
+    fn write_batch(&mut self, records: Vec<OutboxRecord>) -> Result<(), OperationError> {
+        let mut lines = String::new();
+        for record in records.iter() {
+            match serde_json::to_string(record) {
+                Ok(line) => {
+                    lines.push_str(&line);
+                    lines.push('\n');
+                },
+                Err(e) => eprintln!("Skipped outbox record which cannot be serialized; err={}", e),
+            }
+        }
+        if let Err(e) = self.file.write_all(lines.as_bytes()).and_then(|_| self.file.sync_data()) {
+            ret_err!(OperationError::ErrorOutboxIo, "cannot write to {}; err={}", self.path.display(), e);
+        }
+        self.records += records.len();
+
+        for record in records {
+            match record {
+                OutboxRecord::Add { id, sink, created_at_ms, notification } => {
+                    self.next_id = self.next_id.max(id + 1);
+                    self.pending.insert(id, OutboxEntry { id, sink, created_at_ms, notification: *notification });
+                },
+                OutboxRecord::Ack { id } | OutboxRecord::Discard { id } => {
+                    self.pending.remove(&id);
+                },
+            }
+        }
+        self.compact_if_needed()
+    }
+
+    /// Compact the log once it is mostly made of settled records.
+    fn compact_if_needed(&mut self) -> Result<(), OperationError> {
+        if self.records >= COMPACTION_MIN_RECORDS && self.records > self.pending.len() * 2 {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the log to contain only pending entries.
+    /// It is written to a temporary file first then renamed over, so the
+    /// log is never left half-written.
+    fn compact(&mut self) -> Result<(), OperationError> {
+        let tmp_path = self.path.with_extension("compacting");
+        let write_res = File::create(&tmp_path).and_then(|mut tmp| {
+            for entry in self.pending.values() {
+                let line = serde_json::to_string(&entry.to_record()).map_err(std::io::Error::other)?;
+                writeln!(tmp, "{}", line)?;
+            }
+            tmp.sync_all()
+        });
+        if let Err(e) = write_res.and_then(|_| fs::rename(&tmp_path, &self.path)) {
+            ret_err!(OperationError::ErrorOutboxIo, "cannot compact {}; err={}", self.path.display(), e);
+        }
+
+        self.file = open_append(&self.path)?;
+        self.records = self.pending.len();
+        Ok(())
+    }
+}
+
+/// Handle to write into outbox through its writer thread.
+/// Writing only queues the record, so caller is never blocked by disk I/O;
+/// record is on disk once the batch it goes into is synced, shortly after.
+#[derive(Clone)]
+pub struct OutboxHandle {
+    tx: Sender<OutboxRecord>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl OutboxHandle {
+    /// Queue notification to be persisted before sending. It is on disk once
+    /// the batch it goes into is synced, which sending doesn't wait for.
+    ///
+    /// # Arguments
+    /// * `sink` - name of sink notification is to be sent to
+    /// * `notification` - notification to persist
+    ///
+    /// # Returns
+    /// Entry of notification, or `Err` with `OperationError::ErrorOutboxIo`
+    /// if writer thread is gone.
+    pub fn add(&self, sink: &str, notification: &Notification) -> Result<OutboxEntry, OperationError> {
+        let entry = OutboxEntry { id: self.next_id.fetch_add(1, Ordering::Relaxed), sink: sink.to_owned(), created_at_ms: now_ms(), notification: notification.clone() };
+        self.send(entry.to_record())?;
+        Ok(entry)
+    }
+
+    /// Acknowledge that notification is sent.
+    ///
+    /// # Arguments
+    /// * `id` - id of entry
+    pub fn ack(&self, id: u64) -> Result<(), OperationError> {
+        self.send(OutboxRecord::Ack { id })
+    }
+
+    /// Discard notification so it won't be replayed.
+    ///
+    /// # Arguments
+    /// * `id` - id of entry
+    pub fn discard(&self, id: u64) -> Result<(), OperationError> {
+        self.send(OutboxRecord::Discard { id })
+    }
+
+    fn send(&self, record: OutboxRecord) -> Result<(), OperationError> {
+        if self.tx.send(record).is_err() {
+            ret_err!(OperationError::ErrorOutboxIo, "outbox writer is gone");
+        }
+        Ok(())
+    }
+}
+
+/// Open file for appending, creating it if not exist.
+fn open_append(path: &Path) -> Result<File, OperationError> {
+    match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(res) => Ok(res),
+        Err(e) => ret_err!(OperationError::ErrorOutboxIo, "cannot open {} for appending; err={}", path.display(), e),
+    }
+}
+
+/// Get the current timestamp in milliseconds.
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Check whether the entry created at the specified timestamp is older
+/// than `max_age`.
+///
+/// # Arguments
+/// * `created_at_ms` - timestamp in milliseconds when entry was created
+/// * `max_age` - maximum age, `None` for no limit
+pub fn is_stale(created_at_ms: u64, max_age: Option<Duration>) -> bool {
+    match max_age {
+        Some(max_age) => now_ms().saturating_sub(created_at_ms) > max_age.as_millis() as u64,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rust_decimal::Decimal;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("bybit-shiprekt-outbox-{}-{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn notification(text: &str) -> Notification {
+        Notification { text: text.to_owned(), summary: text.to_owned(), worth: Decimal::ONE, event_time: String::new(), details: None, delivery_id: String::new() }
+    }
+
+    fn add(id: u64, sink: &str, text: &str) -> OutboxRecord {
+        OutboxRecord::Add { id, sink: sink.to_owned(), created_at_ms: now_ms(), notification: Box::new(notification(text)) }
+    }
+
+    fn write_lines(path: &Path, records: &[OutboxRecord], trailing: &str) {
+        let mut lines: String = records.iter().map(|r| serde_json::to_string(r).unwrap() + "\n").collect();
+        lines.push_str(trailing);
+        fs::write(path, lines).unwrap();
+    }
+
+    #[test]
+    fn replays_entries_neither_acked_nor_discarded() {
+        let path = temp_path("replay");
+        write_lines(&path, &[
+            add(0, "telegram", "a"),
+            add(1, "discord", "b"),
+            add(2, "telegram", "c"),
+            OutboxRecord::Ack { id: 0 },
+            add(3, "telegram", "d"),
+            OutboxRecord::Discard { id: 2 },
+        ], "{\"op\":\"add\",\"id\":4,\"si");
+
+        let (outbox, entries) = Outbox::open(&path, None).unwrap();
+        let replayed: Vec<(u64, &str, &str)> = entries.iter().map(|e| (e.id, e.sink.as_str(), e.notification.text.as_str())).collect();
+        assert_eq!(replayed, vec![(1, "discord", "b"), (3, "telegram", "d")]);
+        assert_eq!(outbox.next_id, 4);
+
+        // log is compacted to pending entries only
+        let (_, entries) = Outbox::open(&path, None).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn record_without_sink_is_not_replayed() {
+        let path = temp_path("no-sink");
+        fs::write(&path, "{\"op\":\"add\",\"id\":7,\"created_at_ms\":1,\"notification\":{\"text\":\"a\",\"summary\":\"a\",\"worth\":\"1\"}}\n").unwrap();
+        let (_, entries) = Outbox::open(&path, None).unwrap();
+        assert!(entries.is_empty());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn stale_entries_are_not_replayed() {
+        let path = temp_path("stale");
+        write_lines(&path, &[
+            OutboxRecord::Add { id: 0, sink: "telegram".to_owned(), created_at_ms: 1, notification: Box::new(notification("old")) },
+            add(1, "telegram", "new"),
+        ], "");
+        let (_, entries) = Outbox::open(&path, Some(Duration::from_secs(60))).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].notification.text, "new");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn batch_is_written_then_compacted_once_mostly_settled() {
+        let path = temp_path("batch");
+        let (mut outbox, _) = Outbox::open(&path, None).unwrap();
+        for id in 0..COMPACTION_MIN_RECORDS as u64 / 2 {
+            outbox.write_batch(vec![add(id, "telegram", "a"), OutboxRecord::Ack { id }]).unwrap();
+        }
+        outbox.write_batch(vec![add(1000, "telegram", "pending")]).unwrap();
+        assert_eq!(outbox.records, 1);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        outbox.write_batch(vec![add(1001, "telegram", "discarded"), OutboxRecord::Discard { id: 1001 }]).unwrap();
+        let (_, entries) = Outbox::open(&path, None).unwrap();
+        assert_eq!(entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![1000]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn handle_assigns_ids_and_writer_persists_them() {
+        let path = temp_path("writer");
+        let (outbox, _) = Outbox::open(&path, None).unwrap();
+        let handle = outbox.spawn_writer();
+        let a = handle.add("telegram", &notification("a")).unwrap();
+        let b = handle.add("telegram", &notification("b")).unwrap();
+        assert_eq!((a.id, b.id), (0, 1));
+        handle.ack(a.id).unwrap();
+
+        // writer syncs shortly after
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while fs::read_to_string(&path).unwrap_or_default().lines().count() < 3 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(handle);
+        let (_, entries) = Outbox::open(&path, None).unwrap();
+        assert_eq!(entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![1]);
+        let _ = fs::remove_file(&path);
+    }
+}
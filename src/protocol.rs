@@ -0,0 +1,299 @@
+use crate::types::*;
+use crate::v5;
+use crate::filter::SymbolFilter;
+use crate::contract::ContractSpec;
+use crate::liquidation::Liquidation;
+use crate::outbox;
+
+/// Get the endpoints to connect to for the specified protocol version.
+///
+/// v5 has per-symbol topics, so only symbols passing the filter are
+/// subscribed to. Exact symbols in allow-list of the filter, and of sinks
+/// are subscribed to as well even if they are not in the specified symbols.
+/// v2 has a single topic for all symbols, thus filter has to be applied
+/// client-side.
+///
+/// # Arguments
+/// * `version` - protocol version
+/// * `linear_symbols` - symbols to subscribe on linear endpoint; only used by v5
+/// * `inverse_symbols` - symbols to subscribe on inverse endpoint; only used by v5
+/// * `sink_symbols` - exact symbols allowed by filters of sinks; only used by v5
+/// * `filter` - symbol filter
+pub fn endpoints(version: ProtocolVersion, linear_symbols: &[String], inverse_symbols: &[String], sink_symbols: &[String], filter: &SymbolFilter) -> Vec<Endpoint> {
+    let mut linear_symbols = linear_symbols.to_vec();
+    let mut inverse_symbols = inverse_symbols.to_vec();
+    for symbol in filter.exact_allowed_symbols().into_iter().chain(sink_symbols.iter().cloned()) {
+        match ContractSpec::from_symbol(&symbol).category {
+            ContractCategory::LinearPerpetual | ContractCategory::LinearFutures => linear_symbols.push(symbol),
+            ContractCategory::InversePerpetual | ContractCategory::InverseFutures => inverse_symbols.push(symbol),
+        }
+    }
+    let topics = |symbols: Vec<String>| -> Vec<String> {
+        let mut symbols: Vec<String> = symbols.into_iter().filter(|s| filter.matches(s)).collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols.iter().map(|s| v5::liquidation_topic(s)).collect()
+    };
+
+    match version {
+        ProtocolVersion::V2 => vec![
+            Endpoint {
+                name: "inverse",
+                url: "wss://stream.bybit.com/realtime",
+                protocol: version,
+                topics: vec!["liquidation".to_owned()],
+            },
+            Endpoint {
+                name: "linear",
+                url: "wss://stream.bybit.com/realtime_public",
+                protocol: version,
+                topics: vec!["liquidation".to_owned()],
+            },
+        ],
+        ProtocolVersion::V5 => vec![
+            Endpoint {
+                name: "inverse",
+                url: v5::INVERSE_URL,
+                protocol: version,
+                topics: topics(inverse_symbols),
+            },
+            Endpoint {
+                name: "linear",
+                url: v5::LINEAR_URL,
+                protocol: version,
+                topics: topics(linear_symbols),
+            },
+        ],
+    }
+}
+
+/// Build subscription requests for the specified topics of the endpoint.
+/// Topics are split into multiple requests so each one stays within the
+/// exchange's limit of arguments per request.
+///
+/// # Arguments
+/// * `endpoint` - endpoint to subscribe to
+/// * `topics` - topics to subscribe to
+/// * `first_index` - index of the first request, so request ids of a
+///   connection stay unique across calls
+pub fn subscribe_requests(endpoint: &Endpoint, topics: &[String], first_index: usize) -> Vec<SubscribeRequest> {
+    topics.chunks(v5::MAX_SUBSCRIBE_ARGS)
+        .enumerate()
+        .map(|(i, args)| {
+            let req_id = format!("{}-subscribe-{}", endpoint.name, first_index + i);
+            let message = match endpoint.protocol {
+                // v2 doesn't know about request id, it echoes back args instead
+                ProtocolVersion::V2 => serde_json::json!({"op": "subscribe", "args": args}),
+                ProtocolVersion::V5 => serde_json::json!({"req_id": req_id, "op": "subscribe", "args": args}),
+            };
+            SubscribeRequest { req_id, args: args.to_vec(), message: message.to_string() }
+        })
+        .collect()
+}
+
+/// Check whether the response is the acknowledgement of the specified
+/// subscription request.
+/// It is matched by request id if echoed back, otherwise by arguments.
+/// If response carries neither, it is matched by operation only.
+///
+/// # Arguments
+/// * `response` - response as received
+/// * `request` - subscription request
+pub fn is_subscribe_ack(response: &OperationResponse, request: &SubscribeRequest) -> bool {
+    if !response.op.eq_ignore_ascii_case("subscribe") {
+        return false;
+    }
+
+    match (&response.req_id, &response.args) {
+        (Some(req_id), _) if !req_id.is_empty() => *req_id == request.req_id,
+        (_, Some(args)) => *args == request.args,
+        _ => true,
+    }
+}
+
+/// Parse text message received from websocket according to protocol version.
+///
+/// # Arguments
+/// * `version` - protocol version
+/// * `json_str` - text message as received
+pub fn parse_message(version: ProtocolVersion, json_str: &str) -> Result<StreamMessage, serde_json::Error> {
+    match version {
+        ProtocolVersion::V2 => {
+            match serde_json::from_str::<'_, VariantResponse>(json_str)? {
+                VariantResponse::Response(json_obj) => Ok(StreamMessage::Response(OperationResponse {
+                    op: json_obj.request.op,
+                    req_id: None,
+                    args: json_obj.request.args,
+                    success: json_obj.success,
+                    ret_msg: json_obj.ret_msg,
+                    conn_id: json_obj.conn_id,
+                })),
+                VariantResponse::Liquidation(json_obj) => Ok(StreamMessage::Liquidations(normalize(flatten_data(json_obj.data)))),
+            }
+        },
+        ProtocolVersion::V5 => {
+            match serde_json::from_str::<'_, v5::VariantResponse>(json_str)? {
+                v5::VariantResponse::Response(json_obj) => Ok(StreamMessage::Response(OperationResponse {
+                    op: json_obj.op,
+                    req_id: json_obj.req_id,
+                    args: None,
+                    success: json_obj.success,
+                    ret_msg: json_obj.ret_msg,
+                    conn_id: json_obj.conn_id,
+                })),
+                v5::VariantResponse::Liquidation(json_obj) => Ok(StreamMessage::Liquidations(
+                    normalize(flatten_data(json_obj.data).into_iter().map(BybitLiquidationData::from).collect()))),
+            }
+        },
+    }
+}
+
+/// Flatten single, or array of data into `Vec`.
+fn flatten_data<T>(data: GenericData<T>) -> Vec<T> {
+    match data {
+        GenericData::Liquidation(v) => vec![v],
+        GenericData::Liquidations(vs) => vs,
+    }
+}
+
+/// Normalize liquidation data as received into liquidations.
+fn normalize(data: Vec<BybitLiquidationData>) -> Vec<Liquidation> {
+    let received_at_ms = outbox::now_ms();
+    data.into_iter().map(|data| Liquidation::new(data, received_at_ms)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    fn symbols(symbols: &[&str]) -> Vec<String> {
+        symbols.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn v5_topics_are_symbols_passing_filter() {
+        let filter = SymbolFilter::new(&[], &symbols(&["DOGE*"]), vec![ContractCategory::LinearPerpetual, ContractCategory::InversePerpetual]).unwrap();
+        let endpoints = endpoints(ProtocolVersion::V5, &symbols(&["ETHUSDT", "BTCUSDT", "DOGEUSDT", "BTCUSDT-27DEC24", "BTCUSDT"]), &symbols(&["BTCUSD", "BTCUSDM22"]), &[], &filter);
+        assert_eq!(endpoints[0].name, "inverse");
+        assert_eq!(endpoints[0].topics, vec!["allLiquidation.BTCUSD"]);
+        assert_eq!(endpoints[1].name, "linear");
+        assert_eq!(endpoints[1].topics, vec!["allLiquidation.BTCUSDT", "allLiquidation.ETHUSDT"]);
+    }
+
+    #[test]
+    fn v5_topics_include_exact_allowed_symbols() {
+        let filter = SymbolFilter::new(&symbols(&["SOLUSDT", "ETHUSD", "BTC*"]), &[], Vec::new()).unwrap();
+        let endpoints = endpoints(ProtocolVersion::V5, &symbols(&["BTCUSDT", "ETHUSDT"]), &[], &[], &filter);
+        assert_eq!(endpoints[0].topics, vec!["allLiquidation.ETHUSD"]);
+        assert_eq!(endpoints[1].topics, vec!["allLiquidation.BTCUSDT", "allLiquidation.SOLUSDT"]);
+    }
+
+    #[test]
+    fn v5_topics_include_exact_symbols_of_sinks() {
+        let filter = SymbolFilter::new(&[], &symbols(&["XRPUSDT"]), Vec::new()).unwrap();
+        let endpoints = endpoints(ProtocolVersion::V5, &symbols(&["BTCUSDT"]), &[], &symbols(&["ETHUSD", "SOLUSDT", "XRPUSDT", "BTCUSDT"]), &filter);
+        assert_eq!(endpoints[0].topics, vec!["allLiquidation.ETHUSD"]);
+        assert_eq!(endpoints[1].topics, vec!["allLiquidation.BTCUSDT", "allLiquidation.SOLUSDT"]);
+    }
+
+    #[test]
+    fn v2_subscribes_to_all_symbols() {
+        let filter = SymbolFilter::new(&symbols(&["BTCUSDT"]), &[], Vec::new()).unwrap();
+        for endpoint in endpoints(ProtocolVersion::V2, &[], &[], &[], &filter) {
+            assert_eq!(endpoint.topics, vec!["liquidation"]);
+        }
+    }
+
+    #[test]
+    fn subscribe_requests_are_chunked_with_unique_ids() {
+        let filter = SymbolFilter::new(&[], &[], Vec::new()).unwrap();
+        let endpoint = endpoints(ProtocolVersion::V5, &[], &symbols(&["BTCUSD"]), &[], &filter).remove(0);
+        let topics: Vec<String> = (0..12).map(|i| format!("allLiquidation.S{}USDT", i)).collect();
+        let requests = subscribe_requests(&endpoint, &topics, 3);
+        assert_eq!(requests.iter().map(|r| r.req_id.as_str()).collect::<Vec<_>>(), vec!["inverse-subscribe-3", "inverse-subscribe-4"]);
+        assert_eq!((requests[0].args.len(), requests[1].args.len()), (10, 2));
+    }
+
+    fn response(version: ProtocolVersion, json_str: &str) -> OperationResponse {
+        match parse_message(version, json_str).unwrap() {
+            StreamMessage::Response(response) => response,
+            StreamMessage::Liquidations(_) => panic!("not a response: {}", json_str),
+        }
+    }
+
+    #[test]
+    fn v5_subscribe_ack_is_matched_by_req_id() {
+        let filter = SymbolFilter::new(&[], &[], Vec::new()).unwrap();
+        let endpoint = endpoints(ProtocolVersion::V5, &symbols(&["BTCUSDT"]), &[], &[], &filter).into_iter().find(|e| e.name == "linear").unwrap();
+        let topics: Vec<String> = (0..12).map(|i| format!("allLiquidation.S{}USDT", i)).collect();
+        let requests = subscribe_requests(&endpoint, &topics, 0);
+
+        let ack = response(ProtocolVersion::V5, r#"{"success":true,"ret_msg":"","conn_id":"c1","req_id":"linear-subscribe-1","op":"subscribe"}"#);
+        assert!(!is_subscribe_ack(&ack, &requests[0]));
+        assert!(is_subscribe_ack(&ack, &requests[1]));
+
+        let pong = response(ProtocolVersion::V5, r#"{"success":true,"ret_msg":"pong","conn_id":"c1","req_id":"linear-subscribe-1","op":"ping"}"#);
+        assert!(!is_subscribe_ack(&pong, &requests[1]));
+    }
+
+    #[test]
+    fn v2_subscribe_ack_is_matched_by_args() {
+        let requests = [
+            SubscribeRequest { req_id: "v2-subscribe-0".to_owned(), args: symbols(&["liquidation.BTCUSD"]), message: String::new() },
+            SubscribeRequest { req_id: "v2-subscribe-1".to_owned(), args: symbols(&["liquidation.ETHUSD"]), message: String::new() },
+        ];
+
+        let ack = response(ProtocolVersion::V2, r#"{"success":true,"ret_msg":"","conn_id":"c1","request":{"op":"subscribe","args":["liquidation.ETHUSD"]}}"#);
+        assert!(!is_subscribe_ack(&ack, &requests[0]));
+        assert!(is_subscribe_ack(&ack, &requests[1]));
+    }
+
+    fn liquidations(message: StreamMessage) -> Vec<Liquidation> {
+        match message {
+            StreamMessage::Liquidations(liquidations) => liquidations,
+            other => panic!("expected liquidations, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn v2_fractional_qty_and_price_are_exact() {
+        let frame = r#"{"topic":"liquidation.BTCUSDT","data":{"symbol":"BTCUSDT","side":"Sell","price":"64123.50","qty":"0.025","time":1700000000748}}"#;
+        let liquidations = liquidations(parse_message(ProtocolVersion::V2, frame).unwrap());
+        assert_eq!(liquidations.len(), 1);
+        assert_eq!(liquidations[0].qty, Decimal::from_str("0.025").unwrap());
+        assert_eq!(liquidations[0].qty.to_string(), "0.025");
+        assert_eq!(liquidations[0].price.to_string(), "64123.50");
+        assert_eq!(liquidations[0].quantity_string(), "0.025 BTC");
+    }
+
+    #[test]
+    fn v5_fractional_qty_and_tiny_price_are_exact() {
+        let frame = r#"{"topic":"allLiquidation.1000PEPEUSDT","type":"snapshot","ts":1700000000800,"data":[{"T":1700000000748,"s":"1000PEPEUSDT","S":"Buy","v":"1234567.8","p":"0.0012345678"}]}"#;
+        let liquidations = liquidations(parse_message(ProtocolVersion::V5, frame).unwrap());
+        assert_eq!(liquidations.len(), 1);
+        assert_eq!(liquidations[0].qty, Decimal::from_str("1234567.8").unwrap());
+        assert_eq!(liquidations[0].price.to_string(), "0.0012345678");
+        assert_eq!(liquidations[0].quantity_string(), "1,234,567.8 1000PEPE (1,234,567,800 PEPE)");
+    }
+
+    #[test]
+    fn v5_frame_of_several_liquidations_yields_each_with_its_side() {
+        let frame = r#"{"topic":"allLiquidation.BTCUSDT","type":"snapshot","ts":1700000000800,"data":[{"T":1700000000748,"s":"BTCUSDT","S":"Buy","v":"0.5","p":"64000"},{"T":1700000000749,"s":"BTCUSDT","S":"Sell","v":"0.1","p":"64100.5"}]}"#;
+        let liquidations = liquidations(parse_message(ProtocolVersion::V5, frame).unwrap());
+        assert_eq!(liquidations.len(), 2);
+
+        assert_eq!(liquidations[0].symbol, "BTCUSDT");
+        assert_eq!(liquidations[0].side, Side::Buy);
+        assert_eq!(liquidations[0].position_side(), PositionSide::Long);
+        assert_eq!(liquidations[0].event_time_ms, 1_700_000_000_748);
+        assert_eq!(liquidations[0].worth, Decimal::from(32000));
+
+        assert_eq!(liquidations[1].side, Side::Sell);
+        assert_eq!(liquidations[1].position_side(), PositionSide::Short);
+        assert_eq!(liquidations[1].event_time_ms, 1_700_000_000_749);
+        assert_eq!(liquidations[1].worth, Decimal::from_str("6410.05").unwrap());
+    }
+}
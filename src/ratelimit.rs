@@ -0,0 +1,169 @@
+use tokio::time::Instant;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Token bucket limiting rate of sending.
+/// Bucket starts full, then refills continuously at the configured rate up
+/// to its capacity.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Create a new bucket.
+    ///
+    /// # Arguments
+    /// * `capacity` - maximum number of tokens i.e. burst size
+    /// * `refill_per_sec` - number of tokens refilled per second
+    pub fn new(capacity: u32, refill_per_sec: f64) -> Self {
+        let capacity = capacity.max(1) as f64;
+        Self { capacity, tokens: capacity, refill_per_sec, last_refill: Instant::now(), paused_until: None }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Get the instant in which a token will be available.
+    pub fn ready_at(&mut self) -> Instant {
+        let now = Instant::now();
+        self.refill(now);
+
+        let token_ready_at = if self.tokens >= 1.0 || self.refill_per_sec <= 0.0 {
+            now
+        }
+        else {
+            now + Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec)
+        };
+
+        match self.paused_until {
+            Some(paused_until) if paused_until > token_ready_at => paused_until,
+            _ => token_ready_at,
+        }
+    }
+
+    /// Take a token if available.
+    ///
+    /// # Returns
+    /// True if token is taken, otherwise false.
+    pub fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        self.refill(now);
+
+        if let Some(paused_until) = self.paused_until {
+            if paused_until > now {
+                return false;
+            }
+            self.paused_until = None;
+        }
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// Pause the bucket so no token is available until the specified
+    /// duration passes e.g. as told by server's `retry_after`.
+    /// Bucket is also emptied so sending resumes slowly afterwards.
+    ///
+    /// # Arguments
+    /// * `duration` - duration to pause
+    pub fn pause_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.paused_until = Some(self.paused_until.map_or(until, |p| p.max(until)));
+        self.tokens = 0.0;
+    }
+}
+
+/// Rate limiter with a separate token bucket for each key e.g. chat id.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: u32,
+    refill_per_sec: f64,
+    buckets: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter.
+    ///
+    /// # Arguments
+    /// * `rate_per_minute` - number of sends allowed per minute for each key
+    /// * `burst` - number of sends allowed in a burst for each key
+    pub fn new(rate_per_minute: u32, burst: u32) -> Self {
+        Self { capacity: burst, refill_per_sec: rate_per_minute as f64 / 60.0, buckets: HashMap::new() }
+    }
+
+    /// Get the bucket of the specified key, creating it if needed.
+    ///
+    /// # Arguments
+    /// * `key` - key e.g. chat id
+    pub fn bucket(&mut self, key: &str) -> &mut TokenBucket {
+        let (capacity, refill_per_sec) = (self.capacity, self.refill_per_sec);
+        self.buckets.entry(key.to_owned()).or_insert_with(|| TokenBucket::new(capacity, refill_per_sec))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_starts_full_then_refills_at_rate() {
+        let mut bucket = TokenBucket::new(2, 0.5);
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+        assert_eq!(bucket.ready_at(), Instant::now() + Duration::from_secs(2));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(!bucket.try_acquire());
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_refills_up_to_capacity() {
+        let mut bucket = TokenBucket::new(3, 1.0);
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        tokio::time::advance(Duration::from_secs(60)).await;
+        for _ in 0..3 {
+            assert!(bucket.try_acquire());
+        }
+        assert!(!bucket.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_bucket_is_emptied_until_pause_passes() {
+        let mut bucket = TokenBucket::new(5, 1.0);
+        bucket.pause_for(Duration::from_secs(10));
+        assert_eq!(bucket.ready_at(), Instant::now() + Duration::from_secs(10));
+        assert!(!bucket.try_acquire());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        // refilled while paused, up to capacity
+        for _ in 0..5 {
+            assert!(bucket.try_acquire());
+        }
+        assert!(!bucket.try_acquire());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_key_has_its_own_bucket() {
+        let mut limiter = RateLimiter::new(60, 1);
+        assert!(limiter.bucket("telegram:-100123").try_acquire());
+        assert!(!limiter.bucket("telegram:-100123").try_acquire());
+        assert!(limiter.bucket("telegram:-100456").try_acquire());
+    }
+}
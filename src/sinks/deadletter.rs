@@ -0,0 +1,93 @@
+use crate::outbox;
+
+use serde_json::json;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use std::path::PathBuf;
+
+/// File to append notifications given up to, one JSON object per line, so
+/// nothing meant for machine consumers is lost silently.
+/// It is written asynchronously, so delivery task never blocks on disk I/O.
+pub struct DeadLetterFile {
+    /// Path of file, `None` not to write any
+    path: Option<PathBuf>,
+
+    /// Serialize appending to file
+    lock: Mutex<()>,
+}
+
+impl DeadLetterFile {
+    /// Create a new dead-letter file.
+    ///
+    /// # Arguments
+    /// * `path` - path of file to append to, `None` not to write any
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path, lock: Mutex::new(()) }
+    }
+
+    /// Create dead-letter file from environment variable
+    /// `HX_BYBIT_SHIPREKT_<SINK>_DEAD_LETTER_PATH`; empty to disable.
+    ///
+    /// # Arguments
+    /// * `sink_env_name` - name of sink as used in its environment variables e.g. `WEBHOOK`
+    /// * `default_path` - path if not defined
+    pub fn from_env(sink_env_name: &str, default_path: &str) -> Self {
+        let path = std::env::var(format!("HX_BYBIT_SHIPREKT_{}_DEAD_LETTER_PATH", sink_env_name)).unwrap_or_else(|_| default_path.to_owned());
+        Self::new(if path.is_empty() { None } else { Some(PathBuf::from(path)) })
+    }
+
+    /// Append the document given up along with the reason. Failure is
+    /// logged as there is nowhere else to put it.
+    ///
+    /// # Arguments
+    /// * `sink_name` - name of sink, used in logging
+    /// * `reason` - reason it is given up e.g. error of the last attempt
+    /// * `document` - document as it would have been sent
+    pub async fn append(&self, sink_name: &str, reason: &str, document: serde_json::Value) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let line = format!("{}\n", json!({
+            "failed_at_ms": outbox::now_ms(),
+            "error": reason,
+            "document": document,
+        }));
+        let _guard = self.lock.lock().await;
+        let res = match OpenOptions::new().create(true).append(true).open(path).await {
+            // written by a blocking task behind the scenes, flush to wait for it
+            Ok(mut file) => match file.write_all(line.as_bytes()).await {
+                Ok(_) => file.flush().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            eprintln!("[{}] cannot write to dead-letter file {}; err={}", sink_name, path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_are_appended_one_per_line() {
+        let path = std::env::temp_dir().join(format!("bybit-shiprekt-dead-letter-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let file = DeadLetterFile::new(Some(path.clone()));
+        file.append("webhook", "HTTP 400: bad", json!({"type": "notice"})).await;
+        file.append("webhook", "backlog is full", json!({"type": "liquidation"})).await;
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&path).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["error"], "HTTP 400: bad");
+        assert_eq!(records[1]["document"]["type"], "liquidation");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::types::{OperationError, PositionSide};
+use crate::delivery::Notification;
+use crate::liquidation::Liquidation;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::http;
+use crate::utils;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use isahc::HttpClient;
+use serde_json::json;
+use tokio::time::Instant;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Maximum length of content of a discord message in characters
+const DISCORD_MAX_MESSAGE_LEN: usize = 2000;
+
+/// Timeout of each request to discord
+const DISCORD_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Color of embed of liquidated long position; red
+const DISCORD_LONG_COLOR: u32 = 0xE74C3C;
+
+/// Color of embed of liquidated short position; green
+const DISCORD_SHORT_COLOR: u32 = 0x2ECC71;
+
+/// Sink posting notifications to a discord webhook, rendering liquidation as
+/// a rich embed.
+pub struct DiscordSink {
+    client: HttpClient,
+    webhook_url: String,
+    username: Option<String>,
+    health: HealthTracker,
+
+    /// Instant until which rate limit bucket of webhook is exhausted as told
+    /// by its rate limit headers
+    blocked_until: Mutex<Option<Instant>>,
+}
+
+impl DiscordSink {
+    /// Create a new discord sink.
+    ///
+    /// # Arguments
+    /// * `webhook_url` - URL of discord webhook
+    /// * `username` - username to post as overriding the default of webhook, if any
+    pub fn new(webhook_url: &str, username: Option<String>) -> Result<Self, OperationError> {
+        Ok(Self {
+            client: http::create_client(DISCORD_REQUEST_TIMEOUT)?,
+            webhook_url: webhook_url.to_owned(),
+            username,
+            health: HealthTracker::default(),
+            blocked_until: Mutex::new(None),
+        })
+    }
+
+    /// Create discord sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_DISCORD_WEBHOOK_URL`, and optionally
+    /// `HX_BYBIT_SHIPREKT_DISCORD_USERNAME`.
+    ///
+    /// # Returns
+    /// `None` if webhook URL is not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        match std::env::var("HX_BYBIT_SHIPREKT_DISCORD_WEBHOOK_URL") {
+            Ok(webhook_url) if !webhook_url.is_empty() => Ok(Some(Self::new(&webhook_url, std::env::var("HX_BYBIT_SHIPREKT_DISCORD_USERNAME").ok())?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Form the JSON payload of message of the notification.
+    fn payload(&self, notification: &Notification) -> serde_json::Value {
+        let mut payload = match &notification.details {
+            Some(details) => json!({ "embeds": [ liquidation_embed(details, notification.worth, &notification.event_time) ] }),
+            None => json!({ "content": notification.text }),
+        };
+        if let Some(username) = &self.username {
+            payload["username"] = json!(username);
+        }
+        payload
+    }
+
+    /// Remember when rate limit bucket resets if it is exhausted as of
+    /// headers of the response.
+    fn track_rate_limit(&self, response: &http::HttpResponse) {
+        let remaining = response.header_f64("x-ratelimit-remaining");
+        let reset_after = response.header_f64("x-ratelimit-reset-after");
+        if let (Some(remaining), Some(reset_after)) = (remaining, reset_after) {
+            if remaining < 1.0 {
+                *self.lock_blocked_until() = Some(Instant::now() + Duration::from_secs_f64(reset_after.max(0.0)));
+            }
+        }
+    }
+
+    fn lock_blocked_until(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.blocked_until.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl NotificationSink for DiscordSink {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn destination(&self) -> &str {
+        &self.webhook_url
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // don't knowingly hit the limit, 429 counts towards being banned
+        let blocked_until = *self.lock_blocked_until();
+        if let Some(blocked_until) = blocked_until {
+            let now = Instant::now();
+            if blocked_until > now {
+                return Err(SinkError::RetryAfter(blocked_until - now));
+            }
+        }
+
+        let res = match http::post_json(&self.client, &self.webhook_url, &self.payload(notification), &[]).await {
+            Ok(response) => {
+                self.track_rate_limit(&response);
+                if response.is_success() {
+                    Ok(())
+                }
+                else if response.status == 429 {
+                    Err(discord_retry_after(&response))
+                }
+                else {
+                    Err(response.to_sink_error())
+                }
+            },
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    fn max_message_len(&self) -> usize {
+        DISCORD_MAX_MESSAGE_LEN
+    }
+}
+
+/// Form the embed of liquidation.
+///
+/// # Arguments
+/// * `details` - details of liquidation
+/// * `worth` - worth in USD
+/// * `event_time` - time of liquidation as displayed to the sink
+fn liquidation_embed(details: &Liquidation, worth: Decimal, event_time: &str) -> serde_json::Value {
+    let color = if details.position_side() == PositionSide::Long { DISCORD_LONG_COLOR } else { DISCORD_SHORT_COLOR };
+    json!({
+        "title": format!("{} position liquidated on {}", details.position_side(), details.symbol),
+        "color": color,
+        "fields": [
+            { "name": "Symbol", "value": details.symbol, "inline": true },
+            { "name": "Side", "value": details.position_side().to_string(), "inline": true },
+            { "name": "Contract", "value": details.kind.type_name(), "inline": true },
+            { "name": "Quantity", "value": details.quantity_string(), "inline": true },
+            { "name": "Bankruptcy price", "value": format!("${}", utils::format_decimal(details.price)), "inline": true },
+            { "name": "Worth", "value": format!("${}", utils::format_worth(worth)), "inline": true },
+        ],
+        "footer": { "text": format!("Bybit · {}", event_time) },
+    })
+}
+
+/// Get the duration to wait from rate limited response of discord.
+/// Discord tells it via `retry_after` (in seconds) in body, as well as
+/// `Retry-After` header.
+fn discord_retry_after(response: &http::HttpResponse) -> SinkError {
+    let retry_after = serde_json::from_str::<serde_json::Value>(&response.body).ok()
+        .and_then(|body| body["retry_after"].as_f64());
+    match retry_after {
+        Some(secs) => SinkError::RetryAfter(Duration::from_secs_f64(secs.max(0.0))),
+        None => response.to_sink_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+    use crate::sinks::http::test_server;
+    use crate::sinks::test_notification;
+
+    fn liquidation_notification() -> Notification {
+        test_notification::liquidation("BTCUSDT", Side::Buy, "64123.5", "0.025")
+    }
+
+    fn text_notification() -> Notification {
+        test_notification::notice("summary")
+    }
+
+    #[tokio::test]
+    async fn liquidation_is_posted_as_embed() {
+        let (url, server) = test_server::serve(vec![(204, vec![], "")]).await;
+        let sink = DiscordSink::new(&format!("{}/api/webhooks/1/token", url), Some("shiprekt".to_owned())).unwrap();
+        assert!(sink.send(&liquidation_notification()).await.is_ok());
+
+        let received = server.await.unwrap();
+        assert!(received[0].head.starts_with("POST /api/webhooks/1/token "));
+        let body: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(body["username"], "shiprekt");
+        let embed = &body["embeds"][0];
+        assert_eq!(embed["title"], "Long position liquidated on BTCUSDT");
+        assert_eq!(embed["color"], DISCORD_LONG_COLOR);
+        assert_eq!(embed["footer"]["text"], "Bybit · 2023-11-15 05:13:20.123 +07");
+        assert!(embed.get("timestamp").is_none());
+        let fields: Vec<(&str, &str)> = embed["fields"].as_array().unwrap().iter()
+            .map(|f| (f["name"].as_str().unwrap(), f["value"].as_str().unwrap()))
+            .collect();
+        assert_eq!(fields, vec![
+            ("Symbol", "BTCUSDT"),
+            ("Side", "Long"),
+            ("Contract", "Perpetual futures"),
+            ("Quantity", "0.025 BTC"),
+            ("Bankruptcy price", "$64,123.5"),
+            ("Worth", "$1,603.088"),
+        ]);
+    }
+
+    #[test]
+    fn notification_without_details_is_posted_as_content() {
+        let sink = DiscordSink::new("http://127.0.0.1:1", None).unwrap();
+        let payload = sink.payload(&text_notification());
+        assert_eq!(payload, json!({ "content": "summary" }));
+    }
+
+    #[tokio::test]
+    async fn retry_after_of_body_is_honored_on_429() {
+        let (url, server) = test_server::serve(vec![
+            (429, vec![("retry-after", "5")], r#"{"message": "You are being rate limited.", "retry_after": 1.5, "global": false}"#),
+            (429, vec![("retry-after", "3")], "rate limited"),
+        ]).await;
+        let sink = DiscordSink::new(&url, None).unwrap();
+        match sink.send(&text_notification()).await {
+            Err(SinkError::RetryAfter(retry_after)) => assert_eq!(retry_after, Duration::from_millis(1500)),
+            res => panic!("unexpected {:?}", res),
+        }
+        // header is the fallback if body doesn't tell
+        match sink.send(&text_notification()).await {
+            Err(SinkError::RetryAfter(retry_after)) => assert_eq!(retry_after, Duration::from_secs(3)),
+            res => panic!("unexpected {:?}", res),
+        }
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn exhausted_rate_limit_bucket_is_waited_for_without_sending() {
+        let (url, server) = test_server::serve(vec![
+            (204, vec![("x-ratelimit-remaining", "0"), ("x-ratelimit-reset-after", "2.5")], ""),
+        ]).await;
+        let sink = DiscordSink::new(&url, None).unwrap();
+        assert!(sink.send(&text_notification()).await.is_ok());
+        match sink.send(&text_notification()).await {
+            Err(SinkError::RetryAfter(retry_after)) => assert!(retry_after > Duration::from_secs(2) && retry_after <= Duration::from_millis(2500)),
+            res => panic!("unexpected {:?}", res),
+        }
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn remaining_requests_of_bucket_are_not_waited_for() {
+        let (url, server) = test_server::serve(vec![
+            (204, vec![("x-ratelimit-remaining", "4"), ("x-ratelimit-reset-after", "2.5")], ""),
+            (204, vec![], ""),
+        ]).await;
+        let sink = DiscordSink::new(&url, None).unwrap();
+        assert!(sink.send(&text_notification()).await.is_ok());
+        assert!(sink.send(&text_notification()).await.is_ok());
+        assert_eq!(server.await.unwrap().len(), 2);
+    }
+}
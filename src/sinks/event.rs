@@ -0,0 +1,133 @@
+use crate::types::{PositionSide, ContractKind, SettleCurrency};
+use crate::delivery::Notification;
+use crate::liquidation::Liquidation;
+use crate::utils;
+
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+
+/// Version of schema of normalized liquidation event
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Normalized liquidation event for machine consumers.
+#[derive(Debug, serde::Serialize)]
+pub struct LiquidationEvent {
+    /// Version of schema
+    pub version: u32,
+
+    /// Id derived from content of liquidation; the same liquidation always
+    /// gets the same id
+    pub id: String,
+
+    /// Exchange
+    pub exchange: &'static str,
+
+    /// Symbol; ticker
+    pub symbol: String,
+
+    /// Side of position liquidated; `long`, or `short`
+    pub side: PositionSide,
+
+    /// Quantity as a decimal string of exchange's precision e.g. `"0.025"`
+    pub qty: Decimal,
+
+    /// Unit of quantity; `USD` contracts for inverse, base coin for linear
+    pub qty_currency: String,
+
+    /// Size in base coin as a decimal string
+    pub size: Decimal,
+
+    /// Base coin e.g. `BTC`
+    pub size_currency: String,
+
+    /// Currency margin, and PnL are settled in e.g. `BTC` for inverse, or
+    /// `USDT` for linear
+    pub settle_currency: SettleCurrency,
+
+    /// Bankruptcy price as a decimal string of exchange's precision
+    pub price: Decimal,
+
+    /// Worth in USD
+    pub worth: Decimal,
+
+    /// Kind of contract e.g. `linear_perpetual`, or `usdc_perpetual`
+    pub contract_kind: ContractKind,
+
+    /// Timestamp in milliseconds as of exchange
+    pub exchange_ts_ms: u64,
+
+    /// Timestamp in milliseconds when it was received
+    pub received_ts_ms: u64,
+
+    /// Milliseconds from timestamp as of exchange to when it was received;
+    /// negative if local clock is behind that of exchange
+    pub latency_ms: i64,
+}
+
+impl LiquidationEvent {
+    /// Create event from notification of a single liquidation.
+    ///
+    /// # Returns
+    /// `None` if notification is not of a single liquidation e.g. summary.
+    pub fn from_notification(notification: &Notification) -> Option<Self> {
+        let liquidation = notification.details.as_ref()?;
+        Some(Self {
+            version: EVENT_SCHEMA_VERSION,
+            id: event_id(liquidation),
+            exchange: "bybit",
+            symbol: liquidation.symbol.clone(),
+            side: liquidation.position_side(),
+            qty: liquidation.qty,
+            qty_currency: liquidation.qty_currency.clone(),
+            size: liquidation.size,
+            size_currency: liquidation.size_currency.clone(),
+            settle_currency: liquidation.settle_currency.clone(),
+            price: liquidation.price,
+            worth: notification.worth,
+            contract_kind: liquidation.kind,
+            exchange_ts_ms: liquidation.event_time_ms,
+            received_ts_ms: liquidation.received_at_ms,
+            latency_ms: liquidation.latency_ms(),
+        })
+    }
+}
+
+/// Derive id of liquidation from its symbol, side, time, price, and qty.
+/// Exchange doesn't give liquidation an id, but these together identify it,
+/// so the same liquidation received again e.g. replayed after reconnect gets
+/// the same id for consumers to de-duplicate. Price, and qty are normalized
+/// so trailing zeros don't change the id.
+///
+/// # Arguments
+/// * `liquidation` - liquidation
+pub fn event_id(liquidation: &Liquidation) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}|{}|{}|{}|{}", liquidation.symbol, liquidation.position_side(), liquidation.event_time_ms, liquidation.price.normalize(), liquidation.qty.normalize()));
+    utils::to_hex(&hasher.finalize()[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    fn notification(price: &str, qty: &str) -> Notification {
+        test_notification::liquidation("BTCUSDT", Side::Buy, price, qty)
+    }
+
+    #[test]
+    fn event_is_of_first_schema_version() {
+        let event = LiquidationEvent::from_notification(&notification("64000", "0.5")).unwrap();
+        assert_eq!(event.version, 1);
+        assert_eq!(serde_json::to_value(&event).unwrap()["version"], 1);
+    }
+
+    #[test]
+    fn event_id_ignores_trailing_zeros() {
+        let event = LiquidationEvent::from_notification(&notification("64000", "0.5")).unwrap();
+        let padded = LiquidationEvent::from_notification(&notification("64000.00", "0.500")).unwrap();
+        assert_eq!(event.id, padded.id);
+        assert_eq!(event.id.len(), 32);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::types::OperationError;
+use crate::sinks::SinkError;
+
+use isahc::{AsyncReadResponseExt, HttpClient, Request};
+use isahc::config::Configurable;
+use isahc::http::HeaderMap;
+
+use std::time::Duration;
+
+/// Response of HTTP request as needed by sinks.
+pub struct HttpResponse {
+    /// Status code
+    pub status: u16,
+
+    /// Headers
+    pub headers: HeaderMap,
+
+    /// Body as text
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Get value of the header parsed as number.
+    ///
+    /// # Arguments
+    /// * `name` - name of header
+    pub fn header_f64(&self, name: &str) -> Option<f64> {
+        self.headers.get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok())
+    }
+
+    /// Check whether status code is of success.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Convert unsuccessful response into the error of sending.
+    /// 429 is rate limited, honoring `Retry-After` header (in seconds) if
+    /// any. 408, and 5xx might succeed if retry, other 4xx won't.
+    pub fn to_sink_error(&self) -> SinkError {
+        let msg = format!("HTTP {}: {}", self.status, self.body.trim());
+        match self.status {
+            429 => SinkError::RetryAfter(Duration::from_secs_f64(self.header_f64("retry-after").unwrap_or(1.0).max(0.0))),
+            408 | 500..=599 => SinkError::Transient(msg),
+            _ => SinkError::Permanent(msg),
+        }
+    }
+}
+
+/// Post JSON body to the URL.
+///
+/// # Arguments
+/// * `client` - HTTP client
+/// * `url` - URL to post to
+/// * `body` - JSON body
+/// * `headers` - additional headers
+///
+/// # Returns
+/// Response of any status code. `Err` with `SinkError::Transient` if request
+/// cannot be made, or response cannot be read.
+pub async fn post_json(client: &HttpClient, url: &str, body: &serde_json::Value, headers: &[(&str, &str)]) -> Result<HttpResponse, SinkError> {
+    send(client, "POST", url, "application/json", body.to_string().into_bytes(), headers).await
+}
+
+/// Send request with the body to the URL.
+///
+/// # Arguments
+/// * `client` - HTTP client
+/// * `method` - HTTP method e.g. `POST`
+/// * `url` - URL to send to
+/// * `content_type` - content type of body
+/// * `body` - body
+/// * `headers` - additional headers
+///
+/// # Returns
+/// Response of any status code. `Err` with `SinkError::Transient` if request
+/// cannot be made, or response cannot be read.
+pub async fn send(client: &HttpClient, method: &str, url: &str, content_type: &str, body: Vec<u8>, headers: &[(&str, &str)]) -> Result<HttpResponse, SinkError> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(url)
+        .header("content-type", content_type)
+        // curl otherwise waits up to a second for `100 Continue` before
+        // sending body of PUT, or large POST; bodies here are small anyway
+        .header("expect", "");
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    let request = match builder.body(body) {
+        Ok(res) => res,
+        Err(e) => return Err(SinkError::Permanent(format!("cannot form request; err={}", e))),
+    };
+
+    let mut response = match client.send_async(request).await {
+        Ok(res) => res,
+        Err(e) => return Err(SinkError::Transient(format!("cannot send request; err={}", e))),
+    };
+    let body = match response.text().await {
+        Ok(res) => res,
+        Err(e) => return Err(SinkError::Transient(format!("cannot read response; err={}", e))),
+    };
+
+    Ok(HttpResponse { status: response.status().as_u16(), headers: response.headers().clone(), body })
+}
+
+/// Create HTTP client shared by requests of a sink.
+///
+/// # Arguments
+/// * `timeout` - timeout of each request
+///
+/// # Returns
+/// `Err` with `OperationError::ErrorInternalGeneric` if client cannot be
+/// created.
+pub fn create_client(timeout: Duration) -> Result<HttpClient, OperationError> {
+    match HttpClient::builder().timeout(timeout).build() {
+        Ok(res) => Ok(res),
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot create HTTP client; err={}", e),
+    }
+}
+
+/// Local HTTP server for tests of sinks.
+#[cfg(test)]
+pub mod test_server {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Response to serve; status code, headers, and body
+    pub type CannedResponse = (u16, Vec<(&'static str, &'static str)>, &'static str);
+
+    /// Request as received
+    #[derive(Debug)]
+    pub struct ReceivedRequest {
+        /// Request line, and headers
+        pub head: String,
+
+        /// Body as text
+        pub body: String,
+    }
+
+    /// Serve the responses in order, one per connection.
+    ///
+    /// # Arguments
+    /// * `responses` - responses to serve
+    ///
+    /// # Returns
+    /// Base URL of server, and task resolving to requests received.
+    pub async fn serve(responses: Vec<CannedResponse>) -> (String, JoinHandle<Vec<ReceivedRequest>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let task = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for (status, headers, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let head_len = loop {
+                    let mut chunk = [0_u8; 4096];
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..head_len]).to_string();
+                let content_len = head.lines()
+                    .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                while buf.len() < head_len + content_len {
+                    let mut chunk = [0_u8; 4096];
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                received.push(ReceivedRequest { head, body: String::from_utf8_lossy(&buf[head_len..]).to_string() });
+
+                let mut response = format!("HTTP/1.1 {} X\r\ncontent-length: {}\r\nconnection: close\r\n", status, body.len());
+                for (name, value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                response.push_str("\r\n");
+                response.push_str(body);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            received
+        });
+        (url, task)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::event::{self, LiquidationEvent};
+use crate::sinks::deadletter::DeadLetterFile;
+use crate::outbox;
+
+use async_trait::async_trait;
+use rdkafka::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde_json::json;
+
+use std::time::Duration;
+
+/// Timeout of a record to be acknowledged by brokers including retries of
+/// producer itself
+const KAFKA_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of waiting for space in queue of producer
+const KAFKA_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default path of file to append events given up to
+const DEFAULT_DEAD_LETTER_PATH: &str = "bybit-shiprekt-kafka-dead-letter.jsonl";
+
+/// Configuration of kafka sink.
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    /// Comma-separated brokers to bootstrap from e.g. `127.0.0.1:9092`
+    pub brokers: String,
+
+    /// Topic to produce to
+    pub topic: String,
+
+    /// Client id
+    pub client_id: String,
+}
+
+/// Sink producing normalized liquidation events to a kafka topic.
+///
+/// Records are keyed by symbol, so all of a symbol go to the same partition
+/// in order. Producer is idempotent, so its own retries don't duplicate, or
+/// reorder records. Record timestamp is time of liquidation as of exchange,
+/// and headers carry `event_id`, `event_time_ms`, `received_time_ms`, and
+/// `schema_version`.
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+    health: HealthTracker,
+    dead_letter: DeadLetterFile,
+}
+
+impl KafkaSink {
+    /// Create a new kafka sink. Producer connects to brokers in background,
+    /// so brokers being down on start is not an error.
+    ///
+    /// # Arguments
+    /// * `config` - configuration
+    /// * `dead_letter` - file to append events given up to
+    pub fn new(config: KafkaConfig, dead_letter: DeadLetterFile) -> Result<Self, OperationError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("client.id", &config.client_id)
+            // implies acks=all, and bounded in-flight requests
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", KAFKA_MESSAGE_TIMEOUT.as_millis().to_string())
+            .create::<FutureProducer>();
+        let producer = match producer {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot create kafka producer for '{}'; err={}", config.brokers, e),
+        };
+
+        Ok(Self { producer, topic: config.topic, health: HealthTracker::default(), dead_letter })
+    }
+
+    /// Create kafka sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_KAFKA_BROKERS`, and optionally
+    /// `HX_BYBIT_SHIPREKT_KAFKA_TOPIC`, `HX_BYBIT_SHIPREKT_KAFKA_CLIENT_ID`, and
+    /// `HX_BYBIT_SHIPREKT_KAFKA_DEAD_LETTER_PATH`.
+    ///
+    /// # Returns
+    /// `None` if brokers are not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let brokers = match std::env::var("HX_BYBIT_SHIPREKT_KAFKA_BROKERS") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let config = KafkaConfig {
+            brokers,
+            topic: std::env::var("HX_BYBIT_SHIPREKT_KAFKA_TOPIC").unwrap_or_else(|_| "bybit-liquidations".to_owned()),
+            client_id: std::env::var("HX_BYBIT_SHIPREKT_KAFKA_CLIENT_ID").unwrap_or_else(|_| "bybit-shiprekt".to_owned()),
+        };
+        Ok(Some(Self::new(config, DeadLetterFile::from_env("KAFKA", DEFAULT_DEAD_LETTER_PATH))?))
+    }
+}
+
+#[async_trait]
+impl NotificationSink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn is_lossless(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let schema_version = event::EVENT_SCHEMA_VERSION.to_string();
+        let event = LiquidationEvent::from_notification(notification);
+        let payload = payload(notification, event.as_ref()).to_string();
+        let res = match event {
+            Some(event) => {
+                let event_time_ms = event.exchange_ts_ms.to_string();
+                let received_time_ms = event.received_ts_ms.to_string();
+                let headers = OwnedHeaders::new()
+                    .insert(Header { key: "event_id", value: Some(&event.id) })
+                    .insert(Header { key: "event_time_ms", value: Some(&event_time_ms) })
+                    .insert(Header { key: "received_time_ms", value: Some(&received_time_ms) })
+                    .insert(Header { key: "schema_version", value: Some(&schema_version) });
+                let record = FutureRecord::to(&self.topic)
+                    .key(&event.symbol)
+                    .payload(&payload)
+                    .timestamp(event.exchange_ts_ms as i64)
+                    .headers(headers);
+                self.producer.send(record, KAFKA_QUEUE_TIMEOUT).await
+            },
+            // not of a single liquidation e.g. summary as per backlog policy;
+            // no symbol to key by
+            None => {
+                let headers = OwnedHeaders::new()
+                    .insert(Header { key: "schema_version", value: Some(&schema_version) });
+                let record = FutureRecord::<(), _>::to(&self.topic)
+                    .payload(&payload)
+                    .timestamp(outbox::now_ms() as i64)
+                    .headers(headers);
+                self.producer.send(record, KAFKA_QUEUE_TIMEOUT).await
+            },
+        };
+
+        let res = res.map(|_| ()).map_err(|(e, _)| classify_kafka_error(&e));
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    async fn dead_letter(&self, notification: &Notification, reason: &str) {
+        let event = LiquidationEvent::from_notification(notification);
+        let key = event.as_ref().map(|event| event.symbol.clone());
+        let payload = payload(notification, event.as_ref());
+        self.dead_letter.append(self.name(), reason, json!({ "topic": self.topic, "key": key, "payload": payload })).await;
+    }
+}
+
+/// Payload of record for notification, the event if it's of a single
+/// liquidation, or notice otherwise.
+///
+/// # Arguments
+/// * `notification` - notification
+/// * `event` - event of notification if any
+fn payload(notification: &Notification, event: Option<&LiquidationEvent>) -> serde_json::Value {
+    match event {
+        Some(event) => json!(event),
+        None => json!({ "type": "notice", "version": event::EVENT_SCHEMA_VERSION, "text": notification.text, "worth": notification.worth }),
+    }
+}
+
+/// Classify error of producing into that of sink. Record being rejected for
+/// its content, or topic not being accessible won't change by retrying, the
+/// rest e.g. brokers being down, or timeout may.
+fn classify_kafka_error(error: &KafkaError) -> SinkError {
+    match error.rdkafka_error_code() {
+        Some(RDKafkaErrorCode::MessageSizeTooLarge)
+        | Some(RDKafkaErrorCode::InvalidMessage)
+        | Some(RDKafkaErrorCode::InvalidMessageSize)
+        | Some(RDKafkaErrorCode::InvalidTopic)
+        | Some(RDKafkaErrorCode::TopicAuthorizationFailed)
+        | Some(RDKafkaErrorCode::ClusterAuthorizationFailed) => SinkError::Permanent(format!("kafka refused; err={}", error)),
+        _ => SinkError::Transient(format!("cannot produce to kafka; err={}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+
+    #[test]
+    fn notice_payload_is_of_type_notice() {
+        let payload = payload(&test_notification::notice("text"), None);
+        assert_eq!(payload["type"], "notice");
+        assert_eq!(payload["text"], "text");
+        assert_eq!(payload["version"], event::EVENT_SCHEMA_VERSION);
+    }
+}
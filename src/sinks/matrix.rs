@@ -0,0 +1,217 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::liquidation::Liquidation;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::{http, event};
+use crate::utils;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use isahc::HttpClient;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use std::time::Duration;
+
+/// Timeout of each request to matrix homeserver
+const MATRIX_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sink sending notifications as messages into a matrix room via the
+/// client-server API.
+pub struct MatrixSink {
+    client: HttpClient,
+    homeserver_url: Url,
+    access_token: String,
+    room_id: String,
+    health: HealthTracker,
+}
+
+impl MatrixSink {
+    /// Create a new matrix sink.
+    ///
+    /// # Arguments
+    /// * `homeserver_url` - base URL of homeserver e.g. `https://matrix.org`
+    /// * `access_token` - access token of user to send messages as
+    /// * `room_id` - id of room to send messages to e.g. `!abc:matrix.org`
+    pub fn new(homeserver_url: &str, access_token: &str, room_id: &str) -> Result<Self, OperationError> {
+        let homeserver_url = match Url::parse(homeserver_url) {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "invalid matrix homeserver URL '{}'; err={}", homeserver_url, e),
+        };
+        if homeserver_url.cannot_be_a_base() {
+            ret_err!(OperationError::ErrorInternalGeneric, "invalid matrix homeserver URL '{}'", homeserver_url);
+        }
+
+        Ok(Self {
+            client: http::create_client(MATRIX_REQUEST_TIMEOUT)?,
+            homeserver_url,
+            access_token: access_token.to_owned(),
+            room_id: room_id.to_owned(),
+            health: HealthTracker::default(),
+        })
+    }
+
+    /// Create matrix sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_MATRIX_HOMESERVER_URL`,
+    /// `HX_BYBIT_SHIPREKT_MATRIX_ACCESS_TOKEN`, and
+    /// `HX_BYBIT_SHIPREKT_MATRIX_ROOM_ID`.
+    ///
+    /// # Returns
+    /// `None` if homeserver URL is not defined. `Err` with
+    /// `OperationError::ErrorMissingRequiredEnvVar` if it is, but the others
+    /// are not.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let homeserver_url = match std::env::var("HX_BYBIT_SHIPREKT_MATRIX_HOMESERVER_URL") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let access_token = match std::env::var("HX_BYBIT_SHIPREKT_MATRIX_ACCESS_TOKEN") {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_MATRIX_ACCESS_TOKEN not defined; err={}", e),
+        };
+        let room_id = match std::env::var("HX_BYBIT_SHIPREKT_MATRIX_ROOM_ID") {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_MATRIX_ROOM_ID not defined; err={}", e),
+        };
+        Ok(Some(Self::new(&homeserver_url, &access_token, &room_id)?))
+    }
+
+    /// Form URL to send message event with the transaction id into the room.
+    fn send_url(&self, txn_id: &str) -> Url {
+        let mut url = self.homeserver_url.clone();
+        // checked at creation that it can be a base, segments are
+        // percent-encoded where needed, as room id is given by user
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty()
+                .extend(["_matrix", "client", "v3", "rooms", &self.room_id, "send", "m.room.message", txn_id]);
+        }
+        url
+    }
+}
+
+#[async_trait]
+impl NotificationSink for MatrixSink {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    fn destination(&self) -> &str {
+        &self.room_id
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let formatted_body = match &notification.details {
+            Some(details) => liquidation_html(details, notification.worth, &notification.event_time),
+            None => escape_html(&notification.text).replace('\n', "<br>"),
+        };
+        let content = json!({
+            "msgtype": "m.text",
+            "body": notification.text,
+            "format": "org.matrix.custom.html",
+            "formatted_body": formatted_body,
+        });
+
+        let authorization = format!("Bearer {}", self.access_token);
+        let url = self.send_url(&txn_id(notification));
+        let res = match http::send(&self.client, "PUT", url.as_str(), "application/json", content.to_string().into_bytes(), &[("authorization", &authorization)]).await {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) if response.status == 429 => Err(matrix_retry_after(&response)),
+            Ok(response) => Err(response.to_sink_error()),
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+}
+
+/// Derive transaction id of notification.
+/// Homeserver de-duplicates events of the same transaction id, so sending
+/// the same notification again e.g. retry after timeout, or replay from
+/// outbox after restart won't post it twice. Liquidation is identified by
+/// its event id, and others e.g. summary by their id in delivery, as the same
+/// text may well be sent again as a new message.
+///
+/// # Arguments
+/// * `notification` - notification to be sent
+fn txn_id(notification: &Notification) -> String {
+    match &notification.details {
+        Some(details) => format!("shiprekt-{}", event::event_id(details)),
+        None => format!("shiprekt-{}", utils::to_hex(&Sha256::digest(notification.delivery_id.as_bytes()))),
+    }
+}
+
+/// Form HTML version of liquidation message.
+///
+/// # Arguments
+/// * `details` - details of liquidation
+/// * `worth` - worth in USD
+/// * `event_time` - time of liquidation as displayed to the sink
+fn liquidation_html(details: &Liquidation, worth: Decimal, event_time: &str) -> String {
+    format!("Bybit shiprekt a <b>{side}</b> position of {quantity} (worth <b>${worth}</b>) on the <code>{symbol}</code> {contract} contract at ${price} - <i>{datetime}</i>",
+        side=escape_html(&details.position_side().to_string()),
+        quantity=escape_html(&details.quantity_string()),
+        worth=utils::format_worth(worth),
+        symbol=escape_html(&details.symbol),
+        contract=escape_html(details.kind.type_name()),
+        price=utils::format_decimal(details.price),
+        datetime=escape_html(event_time))
+}
+
+/// Escape text to be put into HTML.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Get the duration to wait from rate limited response of matrix homeserver
+/// as told via `retry_after_ms` in body.
+fn matrix_retry_after(response: &http::HttpResponse) -> SinkError {
+    let retry_after_ms = serde_json::from_str::<serde_json::Value>(&response.body).ok()
+        .and_then(|body| body["retry_after_ms"].as_u64());
+    match retry_after_ms {
+        Some(ms) => SinkError::RetryAfter(Duration::from_millis(ms)),
+        None => response.to_sink_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::http::test_server;
+    use crate::sinks::test_notification;
+
+    fn notice(text: &str, delivery_id: &str) -> Notification {
+        Notification { delivery_id: delivery_id.to_owned(), ..test_notification::notice(text) }
+    }
+
+    /// Get the transaction id, the last segment of path of PUT request.
+    fn txn_id_of(head: &str) -> &str {
+        let path = head.split(' ').nth(1).unwrap();
+        path.rsplit('/').next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn retry_of_notice_reuses_its_transaction_id() {
+        let (url, server) = test_server::serve(vec![
+            (500, vec![], "{}"),
+            (200, vec![], r#"{"event_id": "$1"}"#),
+            (200, vec![], r#"{"event_id": "$2"}"#),
+        ]).await;
+        let sink = MatrixSink::new(&url, "token", "!room:example.org").unwrap();
+        assert!(matches!(sink.send(&notice("summary", "outbox-1700000000000-7")).await, Err(SinkError::Transient(_))));
+        assert!(sink.send(&notice("summary", "outbox-1700000000000-7")).await.is_ok());
+        // same text, but another notification
+        assert!(sink.send(&notice("summary", "outbox-1700000000000-8")).await.is_ok());
+
+        let received = server.await.unwrap();
+        assert!(received[0].head.starts_with("PUT /_matrix/client/v3/rooms/!room:example.org/send/m.room.message/shiprekt-"));
+        assert_eq!(txn_id_of(&received[0].head), txn_id_of(&received[1].head));
+        assert_ne!(txn_id_of(&received[1].head), txn_id_of(&received[2].head));
+    }
+}
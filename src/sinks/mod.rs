@@ -0,0 +1,184 @@
+pub mod http;
+pub mod event;
+pub mod deadletter;
+pub mod telegram;
+pub mod discord;
+pub mod slack;
+pub mod matrix;
+pub mod webhook;
+pub mod smtp;
+pub mod mqtt;
+pub mod redis;
+pub mod nats;
+pub mod kafka;
+pub mod push;
+
+use crate::delivery::Notification;
+
+use async_trait::async_trait;
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+/// Number of consecutive failures after which sink is considered unhealthy
+const UNHEALTHY_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// Destination in which notifications are delivered to.
+/// Each configured sink is driven by its own delivery task, so a slow or
+/// broken sink never holds back the others.
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Get the name of sink used in logs, and to key its entries in outbox.
+    /// It has to be unique among configured sinks.
+    fn name(&self) -> &str;
+
+    /// Send notification.
+    ///
+    /// # Arguments
+    /// * `notification` - notification to send
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError>;
+
+    /// Get the current health of sink.
+    async fn health(&self) -> SinkHealth;
+
+    /// Get the destination within the sink e.g. chat id, which the service
+    /// limits rate of by. Default is empty as the sink has only one.
+    fn destination(&self) -> &str {
+        ""
+    }
+
+    /// Get the maximum length of a single message in characters, used when
+    /// merging notifications together.
+    fn max_message_len(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Whether the sink is consumed by machine, so each notification must
+    /// reach it as is; never summarized, nor merged, but dead-lettered once
+    /// evicted from full backlog. Default is false as it is read by human.
+    fn is_lossless(&self) -> bool {
+        false
+    }
+
+    /// Handle notification which is given up after failing permanently,
+    /// exhausting its attempts, being evicted from full backlog, or being
+    /// stale. Default is to do nothing as it is already logged.
+    ///
+    /// # Arguments
+    /// * `notification` - notification given up
+    /// * `reason` - reason it is given up e.g. error of the last attempt
+    async fn dead_letter(&self, _notification: &Notification, _reason: &str) {}
+}
+
+/// Error of sending notification to a sink.
+#[derive(Debug, Clone)]
+pub enum SinkError {
+    /// Rate limited; retry after the specified duration
+    RetryAfter(Duration),
+
+    /// Possibly succeed if retry
+    Transient(String),
+
+    /// Won't succeed no matter how many retries
+    Permanent(String),
+}
+
+/// Health of a sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkHealth {
+    /// Sending works
+    Healthy,
+
+    /// Recent sends failed, but it might recover by itself
+    Degraded(String),
+
+    /// Sends keep failing
+    Unhealthy(String),
+}
+
+/// Tracker of health of a sink from results of its recent sends.
+#[derive(Debug, Default)]
+pub struct HealthTracker {
+    consecutive_failures: AtomicU32,
+    last_error: Mutex<Option<String>>,
+}
+
+impl HealthTracker {
+    /// Record result of a send, and pass it through.
+    ///
+    /// # Arguments
+    /// * `res` - result of sending
+    pub fn record(&self, res: Result<(), SinkError>) -> Result<(), SinkError> {
+        match &res {
+            Ok(_) => {
+                self.consecutive_failures.store(0, Ordering::Relaxed);
+            },
+            // being rate limited is not a sign of sink being broken
+            Err(SinkError::RetryAfter(_)) => (),
+            Err(SinkError::Transient(msg)) | Err(SinkError::Permanent(msg)) => {
+                self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+                *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(msg.clone());
+            },
+        }
+        res
+    }
+
+    /// Get health as of the recent sends.
+    pub fn health(&self) -> SinkHealth {
+        let failures = self.consecutive_failures.load(Ordering::Relaxed);
+        if failures == 0 {
+            return SinkHealth::Healthy;
+        }
+
+        let last_error = self.last_error.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default();
+        let msg = format!("{} consecutive failure(s), last error: {}", failures, last_error);
+        if failures >= UNHEALTHY_CONSECUTIVE_FAILURES {
+            SinkHealth::Unhealthy(msg)
+        }
+        else {
+            SinkHealth::Degraded(msg)
+        }
+    }
+}
+
+/// Notifications for tests of sinks, formed as they are for delivery.
+#[cfg(test)]
+pub mod test_notification {
+    use crate::delivery::Notification;
+    use crate::liquidation::Liquidation;
+    use crate::types::{BybitLiquidationData, Side, TimeDisplay};
+    use crate::utils;
+
+    use rust_decimal::Decimal;
+
+    use std::str::FromStr;
+
+    /// Form notification of a liquidation at 2023-11-15 05:13:20.123 +07,
+    /// received 77ms later.
+    ///
+    /// # Arguments
+    /// * `symbol` - symbol of contract e.g. `BTCUSDT`
+    /// * `side` - side of order as of Bybit
+    /// * `price` - bankruptcy price
+    /// * `qty` - quantity
+    pub fn liquidation(symbol: &str, side: Side, price: &str, qty: &str) -> Notification {
+        let data = BybitLiquidationData {
+            symbol: symbol.to_owned(),
+            side,
+            price: Decimal::from_str(price).unwrap(),
+            qty: Decimal::from_str(qty).unwrap(),
+            time: 1_700_000_000_123,
+        };
+        let time_display = TimeDisplay::new(chrono_tz::Asia::Bangkok, utils::DEFAULT_TIME_FORMAT).unwrap();
+        crate::format_liquidation(&Liquidation::new(data, 1_700_000_000_200), &time_display)
+    }
+
+    /// Form notification of no liquidation e.g. summary of backlog.
+    ///
+    /// # Arguments
+    /// * `text` - text, also as its summary
+    pub fn notice(text: &str) -> Notification {
+        Notification { text: text.to_owned(), summary: text.to_owned(), worth: Decimal::ZERO, event_time: String::new(), details: None, delivery_id: String::new() }
+    }
+}
@@ -0,0 +1,344 @@
+use crate::types::{OperationError, MqttVersion};
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::event::LiquidationEvent;
+use crate::sinks::deadletter::DeadLetterFile;
+use crate::supervisor::BackoffPolicy;
+use crate::utils;
+
+use async_trait::async_trait;
+use serde_json::json;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Capacity of queue of requests between client, and its event loop
+const MQTT_REQUEST_CAPACITY: usize = 64;
+
+/// Duration to hold sending while not connected to broker
+const MQTT_DISCONNECTED_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Keep alive interval of connection to broker
+const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Default path of dead-letter file of MQTT
+const DEFAULT_DEAD_LETTER_PATH: &str = "bybit-shiprekt-mqtt-dead-letter.jsonl";
+
+/// Configuration of MQTT sink.
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    /// Host of broker
+    pub host: String,
+
+    /// Port of broker
+    pub port: u16,
+
+    /// Version of protocol
+    pub version: MqttVersion,
+
+    /// Client id
+    pub client_id: String,
+
+    /// Username, and password to authenticate with, if any
+    pub credentials: Option<(String, String)>,
+
+    /// Prefix of topics e.g. `shiprekt/bybit`
+    pub topic_prefix: String,
+
+    /// QoS of publishing; 0, 1, or 2
+    pub qos: u8,
+
+    /// Whether broker retains the last message of each topic
+    pub retain: bool,
+
+    /// Backoff between attempts to reconnect to broker
+    pub reconnect_backoff: BackoffPolicy,
+}
+
+/// Client of either version of protocol along with QoS to publish with.
+enum MqttClient {
+    V311(rumqttc::AsyncClient, rumqttc::QoS),
+    V5(rumqttc::v5::AsyncClient, rumqttc::v5::mqttbytes::QoS),
+}
+
+/// State of connection to broker as observed by event loop.
+#[derive(Debug, Default)]
+struct ConnectionStatus {
+    connected: AtomicBool,
+    last_error: Mutex<Option<String>>,
+}
+
+impl ConnectionStatus {
+    fn set_connected(&self) {
+        self.connected.store(true, Ordering::Relaxed);
+    }
+
+    /// Mark as disconnected due to the error.
+    ///
+    /// # Returns
+    /// True if it was connected.
+    fn set_disconnected(&self, error: String) -> bool {
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+        self.connected.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Sink publishing liquidation events to MQTT broker on topic
+/// `<prefix>/<symbol>/<side>` with JSON payload.
+/// Connection to broker is maintained by its own event loop task,
+/// reconnecting with backoff independently of websocket connections.
+///
+/// An event is sent, thus acknowledged in outbox, once the client accepts
+/// it rather than once broker acknowledges it, as the client doesn't tell
+/// which PUBACK is of which publish. With QoS above 0 the client resends it
+/// until broker acknowledges it, also across reconnects, so delivery is
+/// at-least-once within one process; events not yet acknowledged by broker
+/// when the process exits are lost.
+pub struct MqttSink {
+    client: MqttClient,
+    config: MqttConfig,
+    status: Arc<ConnectionStatus>,
+    health: HealthTracker,
+
+    /// File to append events given up to
+    dead_letter: DeadLetterFile,
+}
+
+impl MqttSink {
+    /// Create a new MQTT sink, and spawn its event loop. It has to be called
+    /// within tokio runtime.
+    ///
+    /// # Arguments
+    /// * `config` - configuration
+    /// * `dead_letter` - file to append events given up to
+    pub fn new(config: MqttConfig, dead_letter: DeadLetterFile) -> Result<Self, OperationError> {
+        let status = Arc::new(ConnectionStatus::default());
+        // session is kept by broker for QoS above 0, so in-flight messages
+        // are resent after reconnect
+        let clean = config.qos == 0;
+
+        let client = match config.version {
+            MqttVersion::V311 => {
+                let qos = to_qos_v311(config.qos)?;
+                let mut options = rumqttc::MqttOptions::new(&config.client_id, &config.host, config.port);
+                options.set_keep_alive(MQTT_KEEP_ALIVE).set_clean_session(clean);
+                if let Some((username, password)) = &config.credentials {
+                    options.set_credentials(username, password);
+                }
+                let (client, eventloop) = rumqttc::AsyncClient::new(options, MQTT_REQUEST_CAPACITY);
+                tokio::spawn(run_eventloop_v311(eventloop, status.clone(), config.reconnect_backoff.clone()));
+                MqttClient::V311(client, qos)
+            },
+            MqttVersion::V5 => {
+                let qos = to_qos_v5(config.qos)?;
+                let mut options = rumqttc::v5::MqttOptions::new(&config.client_id, &config.host, config.port);
+                options.set_keep_alive(MQTT_KEEP_ALIVE).set_clean_start(clean);
+                if let Some((username, password)) = &config.credentials {
+                    options.set_credentials(username, password);
+                }
+                let (client, eventloop) = rumqttc::v5::AsyncClient::new(options, MQTT_REQUEST_CAPACITY);
+                tokio::spawn(run_eventloop_v5(eventloop, status.clone(), config.reconnect_backoff.clone()));
+                MqttClient::V5(client, qos)
+            },
+        };
+
+        Ok(Self { client, config, status, health: HealthTracker::default(), dead_letter })
+    }
+
+    /// Create MQTT sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_MQTT_HOST`, and optionally
+    /// `HX_BYBIT_SHIPREKT_MQTT_PORT`, `HX_BYBIT_SHIPREKT_MQTT_VERSION`,
+    /// `HX_BYBIT_SHIPREKT_MQTT_CLIENT_ID`, `HX_BYBIT_SHIPREKT_MQTT_USERNAME`,
+    /// `HX_BYBIT_SHIPREKT_MQTT_PASSWORD`, `HX_BYBIT_SHIPREKT_MQTT_TOPIC_PREFIX`,
+    /// `HX_BYBIT_SHIPREKT_MQTT_QOS`, `HX_BYBIT_SHIPREKT_MQTT_RETAIN`, and
+    /// `HX_BYBIT_SHIPREKT_MQTT_DEAD_LETTER_PATH`.
+    ///
+    /// # Returns
+    /// `None` if host is not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let host = match std::env::var("HX_BYBIT_SHIPREKT_MQTT_HOST") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let credentials = match (std::env::var("HX_BYBIT_SHIPREKT_MQTT_USERNAME"), std::env::var("HX_BYBIT_SHIPREKT_MQTT_PASSWORD")) {
+            (Ok(username), Ok(password)) if !username.is_empty() => Some((username, password)),
+            _ => None,
+        };
+        let config = MqttConfig {
+            host,
+            port: utils::get_env_var_or("HX_BYBIT_SHIPREKT_MQTT_PORT", 1883)?,
+            version: utils::get_env_var_or("HX_BYBIT_SHIPREKT_MQTT_VERSION", MqttVersion::V311)?,
+            client_id: std::env::var("HX_BYBIT_SHIPREKT_MQTT_CLIENT_ID").unwrap_or_else(|_| "bybit-shiprekt".to_owned()),
+            credentials,
+            topic_prefix: std::env::var("HX_BYBIT_SHIPREKT_MQTT_TOPIC_PREFIX").unwrap_or_else(|_| "shiprekt/bybit".to_owned()),
+            qos: utils::get_env_var_or("HX_BYBIT_SHIPREKT_MQTT_QOS", 1)?,
+            retain: utils::get_env_var_or("HX_BYBIT_SHIPREKT_MQTT_RETAIN", false)?,
+            reconnect_backoff: BackoffPolicy {
+                initial_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                multiplier: 2.0,
+                max_retries: None,
+            },
+        };
+        Ok(Some(Self::new(config, DeadLetterFile::from_env("MQTT", DEFAULT_DEAD_LETTER_PATH))?))
+    }
+
+    /// Form the topic, and JSON payload to publish for the notification.
+    fn message(&self, notification: &Notification) -> (String, serde_json::Value) {
+        match LiquidationEvent::from_notification(notification) {
+            Some(event) => (format!("{}/{}/{}", self.config.topic_prefix, event.symbol, event.side.to_string().to_lowercase()), json!(event)),
+            None => (format!("{}/notice", self.config.topic_prefix), json!({ "text": notification.text, "worth": notification.worth })),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for MqttSink {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    fn is_lossless(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // let it wait in backlog (and outbox) rather than in client's queue
+        // which is lost on restart; broker being down is not a failure of
+        // the notification, so attempts are not used up
+        if !self.status.connected.load(Ordering::Relaxed) {
+            return Err(SinkError::RetryAfter(MQTT_DISCONNECTED_RETRY_AFTER));
+        }
+
+        let (topic, payload) = self.message(notification);
+        let payload = payload.to_string();
+
+        // done once queued to the client, which resends until acknowledged
+        // by broker only while the process runs
+        let res = match &self.client {
+            MqttClient::V311(client, qos) => client.publish(topic, *qos, self.config.retain, payload).await.map_err(|e| e.to_string()),
+            MqttClient::V5(client, qos) => client.publish(topic, *qos, self.config.retain, payload).await.map_err(|e| e.to_string()),
+        };
+        self.health.record(res.map_err(|e| SinkError::Transient(format!("cannot publish; err={}", e))))
+    }
+
+    async fn dead_letter(&self, notification: &Notification, reason: &str) {
+        let (topic, payload) = self.message(notification);
+        self.dead_letter.append(self.name(), reason, json!({ "topic": topic, "payload": payload })).await;
+    }
+
+    async fn health(&self) -> SinkHealth {
+        if !self.status.connected.load(Ordering::Relaxed) {
+            let last_error = self.status.last_error.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            return SinkHealth::Unhealthy(format!("not connected to broker; last error: {}", last_error.unwrap_or_else(|| "none".to_owned())));
+        }
+        self.health.health()
+    }
+}
+
+fn to_qos_v311(qos: u8) -> Result<rumqttc::QoS, OperationError> {
+    match rumqttc::qos(qos) {
+        Ok(res) => Ok(res),
+        Err(_) => ret_err!(OperationError::ErrorInternalGeneric, "invalid MQTT QoS {}, expected 0, 1, or 2", qos),
+    }
+}
+
+fn to_qos_v5(qos: u8) -> Result<rumqttc::v5::mqttbytes::QoS, OperationError> {
+    match rumqttc::v5::mqttbytes::qos(qos) {
+        Some(res) => Ok(res),
+        None => ret_err!(OperationError::ErrorInternalGeneric, "invalid MQTT QoS {}, expected 0, 1, or 2", qos),
+    }
+}
+
+/// Drive event loop of MQTT 3.1.1 client, reconnecting with backoff on
+/// error.
+async fn run_eventloop_v311(mut eventloop: rumqttc::EventLoop, status: Arc<ConnectionStatus>, backoff: BackoffPolicy) {
+    let mut attempt = 0;
+    loop {
+        match eventloop.poll().await {
+            Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                attempt = 0;
+                status.set_connected();
+                println!("[mqtt] connected to broker");
+            },
+            Ok(_) => (),
+            Err(e) => {
+                attempt += 1;
+                wait_to_reconnect(&status, e.to_string(), &backoff, attempt).await;
+            },
+        }
+    }
+}
+
+/// Drive event loop of MQTT 5 client, reconnecting with backoff on error.
+async fn run_eventloop_v5(mut eventloop: rumqttc::v5::EventLoop, status: Arc<ConnectionStatus>, backoff: BackoffPolicy) {
+    let mut attempt = 0;
+    loop {
+        match eventloop.poll().await {
+            Ok(rumqttc::v5::Event::Incoming(rumqttc::v5::mqttbytes::v5::Packet::ConnAck(_))) => {
+                attempt = 0;
+                status.set_connected();
+                println!("[mqtt] connected to broker");
+            },
+            Ok(_) => (),
+            Err(e) => {
+                attempt += 1;
+                wait_to_reconnect(&status, e.to_string(), &backoff, attempt).await;
+            },
+        }
+    }
+}
+
+/// Mark connection as broken, then wait before event loop reconnects on its
+/// next poll.
+async fn wait_to_reconnect(status: &ConnectionStatus, error: String, backoff: &BackoffPolicy, attempt: u32) {
+    let delay = backoff.delay(attempt);
+    if status.set_disconnected(error.clone()) || attempt == 1 {
+        eprintln!("[mqtt] connection error, reconnecting in {:?}; err={}", delay, error);
+    }
+    tokio::time::sleep(delay).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    fn sink() -> MqttSink {
+        let config = MqttConfig {
+            host: "127.0.0.1".to_owned(),
+            port: 1,
+            version: MqttVersion::V311,
+            client_id: "test".to_owned(),
+            credentials: None,
+            topic_prefix: "shiprekt/bybit".to_owned(),
+            qos: 1,
+            retain: false,
+            reconnect_backoff: BackoffPolicy { initial_delay: Duration::from_secs(60), max_delay: Duration::from_secs(60), multiplier: 2.0, max_retries: None },
+        };
+        MqttSink::new(config, DeadLetterFile::new(None)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn event_is_published_to_topic_of_symbol_and_side() {
+        let (topic, payload) = sink().message(&test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.5"));
+        assert_eq!(topic, "shiprekt/bybit/BTCUSDT/short");
+        assert_eq!(payload["symbol"], "BTCUSDT");
+        assert_eq!(payload["qty"], "0.5");
+    }
+
+    #[tokio::test]
+    async fn notice_is_published_to_topic_of_its_own() {
+        let (topic, payload) = sink().message(&test_notification::notice("summary"));
+        assert_eq!(topic, "shiprekt/bybit/notice");
+        assert_eq!(payload["text"], "summary");
+    }
+
+    #[tokio::test]
+    async fn notification_is_held_while_not_connected_to_broker() {
+        let notification = test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.5");
+        assert!(matches!(sink().send(&notification).await, Err(SinkError::RetryAfter(_))));
+    }
+}
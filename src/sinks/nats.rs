@@ -0,0 +1,267 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::event::{self, LiquidationEvent};
+use crate::sinks::deadletter::DeadLetterFile;
+use crate::supervisor::BackoffPolicy;
+use crate::utils;
+
+use async_nats::connection::State;
+use async_nats::jetstream::context::{PublishError, PublishErrorKind};
+use async_nats::jetstream::message::PublishMessage;
+use async_trait::async_trait;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Timeout of connecting to NATS server
+const NATS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout of waiting for ack of JetStream
+const NATS_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Duration to hold sending while not connected to server
+const NATS_DISCONNECTED_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Default path of file to append events given up to
+const DEFAULT_DEAD_LETTER_PATH: &str = "bybit-shiprekt-nats-dead-letter.jsonl";
+
+/// Authentication to NATS server.
+#[derive(Debug, Clone)]
+pub enum NatsAuth {
+    None,
+    UserPassword(String, String),
+    Token(String),
+}
+
+/// Configuration of NATS sink.
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    /// URL of server e.g. `nats://127.0.0.1:4222`
+    pub url: String,
+
+    /// Authentication
+    pub auth: NatsAuth,
+
+    /// Prefix of subjects e.g. `liquidations.bybit`
+    pub subject_prefix: String,
+
+    /// Backoff between attempts to reconnect to server
+    pub reconnect_backoff: BackoffPolicy,
+}
+
+/// Sink publishing normalized liquidation events to NATS JetStream on subject
+/// `<prefix>.<category>.<symbol>` e.g.
+/// `liquidations.bybit.linear_perpetual.BTCUSDT`, waiting for ack of stream.
+///
+/// Each message carries `Nats-Msg-Id` header of the event id, so JetStream
+/// drops the same liquidation published again e.g. replayed after reconnect,
+/// or restart within duplicate window of the stream. Stream capturing the
+/// subjects is expected to exist.
+pub struct NatsSink {
+    client: async_nats::Client,
+    jetstream: async_nats::jetstream::Context,
+    subject_prefix: String,
+    health: HealthTracker,
+    dead_letter: DeadLetterFile,
+}
+
+impl NatsSink {
+    /// Create a new NATS sink. Connection is established, and re-established
+    /// in background, so NATS server being down on start is not an error.
+    ///
+    /// # Arguments
+    /// * `config` - configuration
+    /// * `dead_letter` - file to append events given up to
+    pub async fn new(config: NatsConfig, dead_letter: DeadLetterFile) -> Result<Self, OperationError> {
+        // client reports error of every attempt to reconnect, only the first
+        // of each outage is logged
+        let error_reported = Arc::new(AtomicBool::new(false));
+        let backoff = config.reconnect_backoff;
+        let mut options = async_nats::ConnectOptions::new()
+            .connection_timeout(NATS_CONNECTION_TIMEOUT)
+            .retry_on_initial_connect()
+            .reconnect_delay_callback(move |attempt| backoff.delay(attempt as u32))
+            .event_callback(move |event| {
+                let error_reported = error_reported.clone();
+                async move {
+                    match event {
+                        async_nats::Event::Connected => {
+                            error_reported.store(false, Ordering::Relaxed);
+                            println!("[nats] connected to server");
+                        },
+                        async_nats::Event::ClientError(e) => {
+                            if !error_reported.swap(true, Ordering::Relaxed) {
+                                eprintln!("[nats] connection error, reconnecting; err={}", e);
+                            }
+                        },
+                        event => eprintln!("[nats] {}", event),
+                    }
+                }
+            });
+        options = match config.auth {
+            NatsAuth::None => options,
+            NatsAuth::UserPassword(username, password) => options.user_and_password(username, password),
+            NatsAuth::Token(token) => options.token(token),
+        };
+
+        let client = match options.connect(config.url.as_str()).await {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot create NATS client for '{}'; err={}", config.url, e),
+        };
+        let mut jetstream = async_nats::jetstream::new(client.clone());
+        jetstream.set_timeout(NATS_ACK_TIMEOUT);
+
+        Ok(Self { client, jetstream, subject_prefix: config.subject_prefix, health: HealthTracker::default(), dead_letter })
+    }
+
+    /// Create NATS sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_NATS_URL`, and optionally
+    /// `HX_BYBIT_SHIPREKT_NATS_USERNAME`, `HX_BYBIT_SHIPREKT_NATS_PASSWORD`,
+    /// `HX_BYBIT_SHIPREKT_NATS_TOKEN`,
+    /// `HX_BYBIT_SHIPREKT_NATS_SUBJECT_PREFIX`, and
+    /// `HX_BYBIT_SHIPREKT_NATS_DEAD_LETTER_PATH`.
+    ///
+    /// # Returns
+    /// `None` if URL is not defined.
+    pub async fn from_env() -> Result<Option<Self>, OperationError> {
+        let url = match std::env::var("HX_BYBIT_SHIPREKT_NATS_URL") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let auth = match (std::env::var("HX_BYBIT_SHIPREKT_NATS_USERNAME"), std::env::var("HX_BYBIT_SHIPREKT_NATS_PASSWORD"), std::env::var("HX_BYBIT_SHIPREKT_NATS_TOKEN")) {
+            (Ok(username), Ok(password), _) if !username.is_empty() => NatsAuth::UserPassword(username, password),
+            (_, _, Ok(token)) if !token.is_empty() => NatsAuth::Token(token),
+            _ => NatsAuth::None,
+        };
+        let config = NatsConfig {
+            url,
+            auth,
+            subject_prefix: std::env::var("HX_BYBIT_SHIPREKT_NATS_SUBJECT_PREFIX").unwrap_or_else(|_| "liquidations.bybit".to_owned()),
+            reconnect_backoff: BackoffPolicy {
+                initial_delay: Duration::from_secs(1),
+                max_delay: Duration::from_secs(60),
+                multiplier: 2.0,
+                max_retries: None,
+            },
+        };
+        Ok(Some(Self::new(config, DeadLetterFile::from_env("NATS", DEFAULT_DEAD_LETTER_PATH)).await?))
+    }
+
+    /// Subject, message id, and payload of message published for
+    /// notification.
+    fn message(&self, notification: &Notification) -> (String, String, serde_json::Value) {
+        // notice has one token less than events, so it doesn't match
+        // wildcard of events e.g. `liquidations.bybit.*.*`
+        match LiquidationEvent::from_notification(notification) {
+            Some(event) => (
+                format!("{}.{}.{}", self.subject_prefix, event.contract_kind, event.symbol),
+                event.id.clone(),
+                json!(event)),
+            None => (
+                format!("{}.notice", self.subject_prefix),
+                utils::to_hex(&Sha256::digest(notification.text.as_bytes())[..16]),
+                json!({ "version": event::EVENT_SCHEMA_VERSION, "text": notification.text, "worth": notification.worth })),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    fn is_lossless(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // let it wait in backlog (and outbox) rather than in client's buffer
+        // which is lost on restart
+        if self.client.connection_state() != State::Connected {
+            return Err(SinkError::RetryAfter(NATS_DISCONNECTED_RETRY_AFTER));
+        }
+
+        let (subject, msg_id, payload) = self.message(notification);
+
+        let publish = PublishMessage::build().payload(payload.to_string().into()).message_id(&msg_id);
+        let res = match self.jetstream.send_publish(subject, publish).await {
+            Ok(ack_future) => ack_future.await,
+            Err(e) => Err(e),
+        };
+        let res = match res {
+            Ok(ack) => {
+                if ack.duplicate {
+                    println!("[nats] already in stream {}, dropped as duplicate; id={}", ack.stream, msg_id);
+                }
+                Ok(())
+            },
+            Err(e) => Err(classify_publish_error(&e)),
+        };
+        self.health.record(res)
+    }
+
+    async fn dead_letter(&self, notification: &Notification, reason: &str) {
+        let (subject, msg_id, payload) = self.message(notification);
+        self.dead_letter.append(self.name(), reason, json!({ "subject": subject, "msg_id": msg_id, "payload": payload })).await;
+    }
+
+    async fn health(&self) -> SinkHealth {
+        match self.client.connection_state() {
+            State::Connected => self.health.health(),
+            state => SinkHealth::Unhealthy(format!("not connected to server; state={}", state)),
+        }
+    }
+}
+
+/// Classify error of publishing to JetStream into that of sink.
+/// No stream capturing the subject, or message being too large won't change
+/// by retrying, the rest e.g. timeout of ack may.
+fn classify_publish_error(error: &PublishError) -> SinkError {
+    match error.kind() {
+        PublishErrorKind::StreamNotFound
+        | PublishErrorKind::MaxPayloadExceeded
+        | PublishErrorKind::WrongLastMessageId
+        | PublishErrorKind::WrongLastSequence => SinkError::Permanent(format!("JetStream refused; err={}", error)),
+        _ => SinkError::Transient(format!("cannot publish to JetStream; err={}", error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    async fn sink() -> NatsSink {
+        let config = NatsConfig {
+            url: "nats://127.0.0.1:1".to_owned(),
+            auth: NatsAuth::None,
+            subject_prefix: "liqs".to_owned(),
+            reconnect_backoff: BackoffPolicy { initial_delay: Duration::from_secs(60), max_delay: Duration::from_secs(60), multiplier: 1.0, max_retries: None },
+        };
+        NatsSink::new(config, DeadLetterFile::new(None)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn event_is_published_to_subject_of_contract_and_symbol_with_its_id() {
+        let notification = test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "2");
+        let (subject, msg_id, payload) = sink().await.message(&notification);
+        assert_eq!(subject, "liqs.linear_perpetual.BTCUSDT");
+        assert_eq!(msg_id, LiquidationEvent::from_notification(&notification).unwrap().id);
+        assert_eq!(payload["symbol"], "BTCUSDT");
+    }
+
+    #[tokio::test]
+    async fn notice_is_published_to_subject_outside_of_events() {
+        let (subject, msg_id, payload) = sink().await.message(&test_notification::notice("text"));
+        assert_eq!(subject, "liqs.notice");
+        assert_eq!(msg_id.len(), 32);
+        assert_eq!(payload["text"], "text");
+        assert_eq!(payload["version"], event::EVENT_SCHEMA_VERSION);
+    }
+}
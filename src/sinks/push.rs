@@ -0,0 +1,332 @@
+use crate::types::{OperationError, PositionSide};
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::http;
+use crate::utils;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use isahc::HttpClient;
+use serde_json::json;
+
+use std::time::Duration;
+
+/// Maximum length of message of ntfy in bytes; longer one is turned into an
+/// attachment
+const NTFY_MAX_MESSAGE_LEN: usize = 4096;
+
+/// Timeout of each request to push server
+const PUSH_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default worth in USD from which notification gets low, default, high, and
+/// urgent priority respectively; below the first it gets min priority
+pub const DEFAULT_PRIORITY_THRESHOLDS: [Decimal; 4] = [
+    Decimal::from_parts(10_000, 0, 0, false, 0),
+    Decimal::from_parts(100_000, 0, 0, false, 0),
+    Decimal::from_parts(1_000_000, 0, 0, false, 0),
+    Decimal::from_parts(10_000_000, 0, 0, false, 0),
+];
+
+/// Priority of push notification, from which that of each service is
+/// mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PushPriority {
+    Min,
+    Low,
+    Default,
+    High,
+    Urgent,
+}
+
+impl PushPriority {
+    /// Get priority of the worth.
+    ///
+    /// # Arguments
+    /// * `worth` - worth in USD
+    /// * `thresholds` - worth from which low, default, high, and urgent
+    ///   priority apply respectively
+    pub fn from_worth(worth: Decimal, thresholds: &[Decimal; 4]) -> Self {
+        match thresholds.iter().filter(|threshold| worth >= **threshold).count() {
+            0 => PushPriority::Min,
+            1 => PushPriority::Low,
+            2 => PushPriority::Default,
+            3 => PushPriority::High,
+            _ => PushPriority::Urgent,
+        }
+    }
+
+    /// Get priority of ntfy; 1 to 5.
+    fn ntfy(self) -> u8 {
+        match self {
+            PushPriority::Min => 1,
+            PushPriority::Low => 2,
+            PushPriority::Default => 3,
+            PushPriority::High => 4,
+            PushPriority::Urgent => 5,
+        }
+    }
+
+    /// Get priority of gotify; 0 to 10. Android app of gotify shows 1 to 3
+    /// silently, 4 to 7 with sound, and 8 or above as heads-up.
+    fn gotify(self) -> u8 {
+        match self {
+            PushPriority::Min => 1,
+            PushPriority::Low => 3,
+            PushPriority::Default => 5,
+            PushPriority::High => 8,
+            PushPriority::Urgent => 10,
+        }
+    }
+}
+
+/// Sink publishing notifications to a topic of ntfy server e.g.
+/// <https://ntfy.sh> as push notifications with priority mapped from worth.
+/// Each topic is a sink of its own with its own symbol filter, so each one
+/// can subscribe to their own coins only.
+pub struct NtfySink {
+    client: HttpClient,
+    url: String,
+    topic: String,
+    access_token: Option<String>,
+    priority_thresholds: [Decimal; 4],
+    health: HealthTracker,
+
+    /// Name of sink including topic e.g. `ntfy/alice-liqs`
+    name: String,
+}
+
+impl NtfySink {
+    /// Create a new ntfy sink.
+    ///
+    /// # Arguments
+    /// * `url` - base URL of ntfy server e.g. `https://ntfy.sh`
+    /// * `topic` - topic to publish to
+    /// * `access_token` - access token of user to publish as, if any
+    /// * `priority_thresholds` - worth from which low, default, high, and
+    ///   urgent priority apply respectively
+    pub fn new(url: &str, topic: &str, access_token: Option<String>, priority_thresholds: [Decimal; 4]) -> Result<Self, OperationError> {
+        Ok(Self {
+            client: http::create_client(PUSH_REQUEST_TIMEOUT)?,
+            url: url.to_owned(),
+            topic: topic.to_owned(),
+            access_token,
+            priority_thresholds,
+            health: HealthTracker::default(),
+            name: format!("ntfy/{}", topic),
+        })
+    }
+
+    /// Create ntfy sinks from environment variables
+    /// `HX_BYBIT_SHIPREKT_NTFY_TOPICS`, and optionally
+    /// `HX_BYBIT_SHIPREKT_NTFY_URL`, `HX_BYBIT_SHIPREKT_NTFY_ACCESS_TOKEN`, and
+    /// `HX_BYBIT_SHIPREKT_PUSH_PRIORITY_THRESHOLDS`.
+    ///
+    /// Topics are semicolon-separated, each optionally followed by `=` and
+    /// comma-separated symbols, or glob patterns e.g.
+    /// `alice-liqs=BTCUSDT,ETH*;team-liqs`. Each topic can only be specified
+    /// once as its sink name keys outbox, and rate limit.
+    ///
+    /// # Returns
+    /// Sink of each topic along with its symbols; empty if there is none.
+    pub fn from_env() -> Result<Vec<(Self, Vec<String>)>, OperationError> {
+        let topics_str = std::env::var("HX_BYBIT_SHIPREKT_NTFY_TOPICS").unwrap_or_default();
+        let url = std::env::var("HX_BYBIT_SHIPREKT_NTFY_URL").unwrap_or_else(|_| "https://ntfy.sh".to_owned());
+        let access_token = std::env::var("HX_BYBIT_SHIPREKT_NTFY_ACCESS_TOKEN").ok().filter(|token| !token.is_empty());
+        let priority_thresholds = priority_thresholds_from_env()?;
+
+        let mut sinks: Vec<(Self, Vec<String>)> = Vec::new();
+        for (topic, symbols) in parse_ntfy_topics(&topics_str)? {
+            sinks.push((Self::new(&url, &topic, access_token.clone(), priority_thresholds)?, symbols));
+        }
+        Ok(sinks)
+    }
+}
+
+/// Parse ntfy topics each optionally followed by `=` and its symbols e.g.
+/// `alice-liqs=BTCUSDT,ETH*;team-liqs`.
+///
+/// # Arguments
+/// * `topics_str` - semicolon-separated topics
+///
+/// # Returns
+/// Each topic along with its symbols, or error if a topic is specified more
+/// than once.
+fn parse_ntfy_topics(topics_str: &str) -> Result<Vec<(String, Vec<String>)>, OperationError> {
+    let mut topics: Vec<(String, Vec<String>)> = Vec::new();
+    for s in topics_str.split(';').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let (topic, symbols) = match s.split_once('=') {
+            Some((topic, symbols_str)) => (topic.trim(), utils::parse_symbol_list(symbols_str)),
+            None => (s, Vec::new()),
+        };
+        if topics.iter().any(|(t, _)| t == topic) {
+            ret_err!(OperationError::ErrorInternalGeneric, "ntfy topic '{}' is specified more than once in HX_BYBIT_SHIPREKT_NTFY_TOPICS", topic);
+        }
+        topics.push((topic.to_owned(), symbols));
+    }
+    Ok(topics)
+}
+
+#[async_trait]
+impl NotificationSink for NtfySink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let priority = PushPriority::from_worth(notification.worth, &self.priority_thresholds);
+        let mut payload = json!({
+            "topic": self.topic,
+            "title": title(notification),
+            "message": notification.text,
+            "priority": priority.ntfy(),
+        });
+        if let Some(details) = &notification.details {
+            // price goes down on long liquidation, and up on short one
+            let tag = if details.position_side() == PositionSide::Long { "chart_with_downwards_trend" } else { "chart_with_upwards_trend" };
+            payload["tags"] = json!([tag]);
+        }
+
+        let authorization = self.access_token.as_ref().map(|token| format!("Bearer {}", token));
+        let headers: Vec<(&str, &str)> = authorization.iter().map(|value| ("authorization", value.as_str())).collect();
+        let res = match http::post_json(&self.client, &self.url, &payload, &headers).await {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => Err(response.to_sink_error()),
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    fn max_message_len(&self) -> usize {
+        NTFY_MAX_MESSAGE_LEN
+    }
+}
+
+/// Sink posting notifications to an application of gotify server as push
+/// notifications with priority mapped from worth.
+pub struct GotifySink {
+    client: HttpClient,
+    message_url: String,
+    app_token: String,
+    priority_thresholds: [Decimal; 4],
+    health: HealthTracker,
+}
+
+impl GotifySink {
+    /// Create a new gotify sink.
+    ///
+    /// # Arguments
+    /// * `url` - base URL of gotify server e.g. `https://gotify.example.com`
+    /// * `app_token` - token of application to post messages as
+    /// * `priority_thresholds` - worth from which low, default, high, and
+    ///   urgent priority apply respectively
+    pub fn new(url: &str, app_token: &str, priority_thresholds: [Decimal; 4]) -> Result<Self, OperationError> {
+        Ok(Self {
+            client: http::create_client(PUSH_REQUEST_TIMEOUT)?,
+            message_url: format!("{}/message", url.trim_end_matches('/')),
+            app_token: app_token.to_owned(),
+            priority_thresholds,
+            health: HealthTracker::default(),
+        })
+    }
+
+    /// Create gotify sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_GOTIFY_URL`, `HX_BYBIT_SHIPREKT_GOTIFY_APP_TOKEN`,
+    /// and optionally `HX_BYBIT_SHIPREKT_PUSH_PRIORITY_THRESHOLDS`.
+    ///
+    /// # Returns
+    /// `None` if URL is not defined. `Err` with
+    /// `OperationError::ErrorMissingRequiredEnvVar` if it is, but application
+    /// token is not.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let url = match std::env::var("HX_BYBIT_SHIPREKT_GOTIFY_URL") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let app_token = match std::env::var("HX_BYBIT_SHIPREKT_GOTIFY_APP_TOKEN") {
+            Ok(res) if !res.is_empty() => res,
+            _ => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_GOTIFY_APP_TOKEN not defined"),
+        };
+        Ok(Some(Self::new(&url, &app_token, priority_thresholds_from_env()?)?))
+    }
+}
+
+#[async_trait]
+impl NotificationSink for GotifySink {
+    fn name(&self) -> &str {
+        "gotify"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let priority = PushPriority::from_worth(notification.worth, &self.priority_thresholds);
+        let payload = json!({
+            "title": title(notification),
+            "message": notification.text,
+            "priority": priority.gotify(),
+        });
+
+        let res = match http::post_json(&self.client, &self.message_url, &payload, &[("x-gotify-key", &self.app_token)]).await {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => Err(response.to_sink_error()),
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+}
+
+/// Form title of push notification, short enough to be read at a glance on
+/// lock screen.
+fn title(notification: &Notification) -> String {
+    match &notification.details {
+        Some(details) => format!("{} {} liquidated ${}", details.symbol, details.position_side(), utils::format_worth(notification.worth)),
+        None => "Bybit liquidations".to_owned(),
+    }
+}
+
+/// Get thresholds of priority of push notifications from environment
+/// variable `HX_BYBIT_SHIPREKT_PUSH_PRIORITY_THRESHOLDS`; 4 comma-separated
+/// ascending worth in USD from which low, default, high, and urgent priority
+/// apply respectively.
+fn priority_thresholds_from_env() -> Result<[Decimal; 4], OperationError> {
+    let thresholds_str = match std::env::var("HX_BYBIT_SHIPREKT_PUSH_PRIORITY_THRESHOLDS") {
+        Ok(res) if !res.is_empty() => res,
+        _ => return Ok(DEFAULT_PRIORITY_THRESHOLDS),
+    };
+
+    let thresholds = thresholds_str.split(',')
+        .map(|s| s.trim().parse::<Decimal>())
+        .collect::<Result<Vec<_>, _>>();
+    match thresholds {
+        Ok(thresholds) if thresholds.len() == 4 && thresholds.windows(2).all(|pair| pair[0] <= pair[1]) => {
+            Ok([thresholds[0], thresholds[1], thresholds[2], thresholds[3]])
+        },
+        _ => ret_err!(OperationError::ErrorInternalGeneric, "invalid HX_BYBIT_SHIPREKT_PUSH_PRIORITY_THRESHOLDS '{}', expected 4 comma-separated ascending worth", thresholds_str),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntfy_topics_with_and_without_symbols() {
+        let topics = parse_ntfy_topics(" alice-liqs = BTCUSDT,ETH* ; team-liqs;").unwrap();
+        assert_eq!(topics, vec![
+            ("alice-liqs".to_owned(), vec!["BTCUSDT".to_owned(), "ETH*".to_owned()]),
+            ("team-liqs".to_owned(), Vec::new()),
+        ]);
+    }
+
+    #[test]
+    fn duplicate_ntfy_topic_is_rejected() {
+        assert!(parse_ntfy_topics("alice-liqs=BTCUSDT;alice-liqs=ETHUSDT").is_err());
+        assert!(parse_ntfy_topics("team-liqs;team-liqs").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::event::{self, LiquidationEvent};
+use crate::sinks::deadletter::DeadLetterFile;
+use crate::utils;
+
+use async_trait::async_trait;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use serde_json::json;
+
+use std::time::Duration;
+
+/// Timeout of connecting to redis server
+const REDIS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Timeout of each command to redis server
+const REDIS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default path of dead-letter file of redis
+const DEFAULT_DEAD_LETTER_PATH: &str = "bybit-shiprekt-redis-dead-letter.jsonl";
+
+/// Configuration of redis sink.
+#[derive(Debug, Clone)]
+pub struct RedisConfig {
+    /// URL of redis server e.g. `redis://127.0.0.1:6379/0`
+    pub url: String,
+
+    /// Key of stream to append events to
+    pub stream_key: String,
+
+    /// Approximate maximum length of stream to trim it to; `None` for no
+    /// trimming
+    pub stream_maxlen: Option<u64>,
+
+    /// Pub/sub channel to also publish events on, if any
+    pub channel: Option<String>,
+}
+
+/// Sink appending liquidation events to a redis stream with `XADD`, and
+/// optionally publishing them on a pub/sub channel.
+///
+/// Each stream entry has the fields of the normalized event with `type`
+/// field of `liquidation`, or `notice` for other notifications. Messages on
+/// the channel are the same JSON documents as posted by webhook sink.
+pub struct RedisSink {
+    connection: ConnectionManager,
+    config: RedisConfig,
+    health: HealthTracker,
+
+    /// File to append events given up to
+    dead_letter: DeadLetterFile,
+}
+
+impl RedisSink {
+    /// Create a new redis sink. It doesn't connect until the first
+    /// notification, and reconnects by itself afterwards, so redis server
+    /// being down on start is not an error. It has to be called within tokio
+    /// runtime.
+    ///
+    /// # Arguments
+    /// * `config` - configuration
+    /// * `dead_letter` - file to append events given up to
+    pub fn new(config: RedisConfig, dead_letter: DeadLetterFile) -> Result<Self, OperationError> {
+        let client = match redis::Client::open(config.url.as_str()) {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "invalid redis URL '{}'; err={}", config.url, e),
+        };
+        let manager_config = ConnectionManagerConfig::new()
+            .set_connection_timeout(Some(REDIS_CONNECTION_TIMEOUT))
+            .set_response_timeout(Some(REDIS_RESPONSE_TIMEOUT))
+            // retry of delivery takes care of the rest
+            .set_number_of_retries(1);
+        let connection = match client.get_connection_manager_lazy(manager_config) {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot create redis connection; err={}", e),
+        };
+
+        Ok(Self { connection, config, health: HealthTracker::default(), dead_letter })
+    }
+
+    /// Create redis sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_REDIS_URL`, and optionally
+    /// `HX_BYBIT_SHIPREKT_REDIS_STREAM_KEY`,
+    /// `HX_BYBIT_SHIPREKT_REDIS_STREAM_MAXLEN`,
+    /// `HX_BYBIT_SHIPREKT_REDIS_CHANNEL`, and
+    /// `HX_BYBIT_SHIPREKT_REDIS_DEAD_LETTER_PATH`.
+    ///
+    /// # Returns
+    /// `None` if URL is not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let url = match std::env::var("HX_BYBIT_SHIPREKT_REDIS_URL") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let config = RedisConfig {
+            url,
+            stream_key: std::env::var("HX_BYBIT_SHIPREKT_REDIS_STREAM_KEY").unwrap_or_else(|_| "shiprekt:bybit:liquidations".to_owned()),
+            // 0 means no trimming
+            stream_maxlen: match utils::get_env_var_or("HX_BYBIT_SHIPREKT_REDIS_STREAM_MAXLEN", 100_000_u64)? {
+                0 => None,
+                maxlen => Some(maxlen),
+            },
+            channel: std::env::var("HX_BYBIT_SHIPREKT_REDIS_CHANNEL").ok().filter(|channel| !channel.is_empty()),
+        };
+        Ok(Some(Self::new(config, DeadLetterFile::from_env("REDIS", DEFAULT_DEAD_LETTER_PATH))?))
+    }
+}
+
+#[async_trait]
+impl NotificationSink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn is_lossless(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let event = LiquidationEvent::from_notification(notification);
+
+        let mut xadd = redis::cmd("XADD");
+        xadd.arg(&self.config.stream_key);
+        if let Some(maxlen) = self.config.stream_maxlen {
+            // approximate trimming is much cheaper for redis
+            xadd.arg("MAXLEN").arg("~").arg(maxlen);
+        }
+        xadd.arg("*");
+        for (field, value) in stream_fields(notification, event.as_ref()) {
+            xadd.arg(field).arg(value);
+        }
+
+        // both, or neither, so a failed send leaves nothing half done; but
+        // if reply of EXEC is lost after it is executed, the retry appends
+        // the event again, so consumers have to dedupe by its `id`
+        let mut pipe = redis::pipe();
+        pipe.atomic().add_command(xadd).ignore();
+        if let Some(channel) = &self.config.channel {
+            let document = match &event {
+                Some(event) => json!({ "type": "liquidation", "event": event }),
+                None => notice_document(notification),
+            };
+            pipe.cmd("PUBLISH").arg(channel).arg(document.to_string()).ignore();
+        }
+
+        // connection manager is a cheap handle to a shared connection
+        let mut connection = self.connection.clone();
+        let res = pipe.query_async::<()>(&mut connection).await
+            .map_err(|e| classify_redis_error(&e));
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    async fn dead_letter(&self, notification: &Notification, reason: &str) {
+        let event = LiquidationEvent::from_notification(notification);
+        let fields: serde_json::Map<String, serde_json::Value> = stream_fields(notification, event.as_ref()).into_iter()
+            .map(|(field, value)| (field, json!(value)))
+            .collect();
+        self.dead_letter.append(self.name(), reason, json!({ "stream_key": self.config.stream_key, "fields": fields })).await;
+    }
+}
+
+/// Form fields of stream entry of the notification.
+///
+/// # Arguments
+/// * `notification` - notification
+/// * `event` - normalized event if notification is of a single liquidation
+fn stream_fields(notification: &Notification, event: Option<&LiquidationEvent>) -> Vec<(String, String)> {
+    let document = match event {
+        Some(event) => {
+            let mut document = json!(event);
+            document["type"] = json!("liquidation");
+            document
+        },
+        None => notice_document(notification),
+    };
+
+    match document {
+        serde_json::Value::Object(map) => map.into_iter()
+            .map(|(field, value)| match value {
+                // without quotes
+                serde_json::Value::String(value) => (field, value),
+                value => (field, value.to_string()),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Form JSON document of notification not of a single liquidation e.g.
+/// summary as per backlog policy.
+fn notice_document(notification: &Notification) -> serde_json::Value {
+    json!({
+        "type": "notice",
+        "version": event::EVENT_SCHEMA_VERSION,
+        "text": notification.text,
+        "worth": notification.worth,
+    })
+}
+
+/// Classify error of redis into that of sink. Errors of connection are
+/// worth retrying, the rest e.g. `WRONGTYPE` of the stream key are not.
+fn classify_redis_error(error: &redis::RedisError) -> SinkError {
+    if error.is_io_error() || error.is_timeout() || error.is_connection_dropped() || error.is_connection_refusal() {
+        SinkError::Transient(format!("cannot reach redis; err={}", error))
+    }
+    else {
+        SinkError::Permanent(format!("redis refused; err={}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    #[test]
+    fn stream_fields_are_of_event_without_quotes() {
+        let notification = test_notification::liquidation("BTCUSD", Side::Buy, "64000", "25000");
+        let event = LiquidationEvent::from_notification(&notification);
+        let fields = stream_fields(&notification, event.as_ref());
+        let field = |name: &str| fields.iter().find(|(f, _)| f == name).map(|(_, v)| v.as_str());
+        assert_eq!(field("type"), Some("liquidation"));
+        assert_eq!(field("symbol"), Some("BTCUSD"));
+        assert_eq!(field("side"), Some("long"));
+        assert_eq!(field("size"), Some("0.390625"));
+        assert_eq!(field("latency_ms"), Some("77"));
+    }
+
+    #[test]
+    fn stream_fields_of_notice_are_of_type_notice() {
+        let notification = test_notification::notice("summary");
+        let fields = stream_fields(&notification, None);
+        let field = |name: &str| fields.iter().find(|(f, _)| f == name).map(|(_, v)| v.as_str());
+        assert_eq!(field("type"), Some("notice"));
+        assert_eq!(field("text"), Some("summary"));
+    }
+}
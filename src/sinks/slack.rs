@@ -0,0 +1,178 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::liquidation::Liquidation;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::http;
+use crate::utils;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use isahc::HttpClient;
+use serde_json::json;
+
+use std::time::Duration;
+
+/// Maximum length of text of a slack message in characters
+const SLACK_MAX_MESSAGE_LEN: usize = 40_000;
+
+/// Timeout of each request to slack
+const SLACK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sink posting notifications to a slack incoming webhook, rendering
+/// liquidation with Block Kit.
+pub struct SlackSink {
+    client: HttpClient,
+    webhook_url: String,
+    health: HealthTracker,
+}
+
+impl SlackSink {
+    /// Create a new slack sink.
+    ///
+    /// # Arguments
+    /// * `webhook_url` - URL of slack incoming webhook
+    pub fn new(webhook_url: &str) -> Result<Self, OperationError> {
+        Ok(Self {
+            client: http::create_client(SLACK_REQUEST_TIMEOUT)?,
+            webhook_url: webhook_url.to_owned(),
+            health: HealthTracker::default(),
+        })
+    }
+
+    /// Create slack sink from environment variable
+    /// `HX_BYBIT_SHIPREKT_SLACK_WEBHOOK_URL`.
+    ///
+    /// # Returns
+    /// `None` if webhook URL is not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        match std::env::var("HX_BYBIT_SHIPREKT_SLACK_WEBHOOK_URL") {
+            Ok(webhook_url) if !webhook_url.is_empty() => Ok(Some(Self::new(&webhook_url)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for SlackSink {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn destination(&self) -> &str {
+        &self.webhook_url
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // `text` is still needed as fallback for notifications of clients
+        let payload = match &notification.details {
+            Some(details) => json!({ "text": notification.text, "blocks": liquidation_blocks(details, notification.worth, &notification.event_time) }),
+            None => json!({ "text": notification.text }),
+        };
+
+        let res = match http::post_json(&self.client, &self.webhook_url, &payload, &[]).await {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => Err(response.to_sink_error()),
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    fn max_message_len(&self) -> usize {
+        SLACK_MAX_MESSAGE_LEN
+    }
+}
+
+/// Form the Block Kit blocks of liquidation; header, fields, and context
+/// with time as displayed to the sink.
+///
+/// # Arguments
+/// * `details` - details of liquidation
+/// * `worth` - worth in USD
+/// * `event_time` - time of liquidation as displayed to the sink
+fn liquidation_blocks(details: &Liquidation, worth: Decimal, event_time: &str) -> serde_json::Value {
+    let field = |name: &str, value: String| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", name, value) });
+
+    json!([
+        {
+            "type": "header",
+            "text": { "type": "plain_text", "text": format!("{} position liquidated on {}", details.position_side(), details.symbol) },
+        },
+        {
+            "type": "section",
+            "fields": [
+                field("Symbol", details.symbol.clone()),
+                field("Side", details.position_side().to_string()),
+                field("Quantity", details.quantity_string()),
+                field("Bankruptcy price", format!("${}", utils::format_decimal(details.price))),
+                field("Worth", format!("${}", utils::format_worth(worth))),
+                field("Contract", details.kind.type_name().to_owned()),
+            ],
+        },
+        {
+            "type": "context",
+            "elements": [ { "type": "plain_text", "text": format!("Bybit · {}", event_time) } ],
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::http::test_server;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    #[tokio::test]
+    async fn liquidation_is_posted_as_blocks_with_text_as_fallback() {
+        let (url, server) = test_server::serve(vec![(200, vec![], "ok")]).await;
+        let sink = SlackSink::new(&format!("{}/services/T0/B0/token", url)).unwrap();
+        let notification = test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.025");
+        assert!(sink.send(&notification).await.is_ok());
+
+        let received = server.await.unwrap();
+        assert!(received[0].head.starts_with("POST /services/T0/B0/token "));
+        let body: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(body["text"], notification.text);
+        let blocks = &body["blocks"];
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[0]["text"]["text"], "Short position liquidated on BTCUSDT");
+        let fields: Vec<&str> = blocks[1]["fields"].as_array().unwrap().iter()
+            .map(|field| field["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec![
+            "*Symbol*\nBTCUSDT",
+            "*Side*\nShort",
+            "*Quantity*\n0.025 BTC",
+            "*Bankruptcy price*\n$64,000",
+            "*Worth*\n$1,600",
+            "*Contract*\nPerpetual futures",
+        ]);
+        assert_eq!(blocks[2]["elements"][0]["type"], "plain_text");
+        assert_eq!(blocks[2]["elements"][0]["text"], "Bybit · 2023-11-15 05:13:20.123 +07");
+    }
+
+    #[tokio::test]
+    async fn notice_is_posted_as_text_only() {
+        let (url, server) = test_server::serve(vec![(200, vec![], "ok")]).await;
+        let sink = SlackSink::new(&url).unwrap();
+        assert!(sink.send(&test_notification::notice("summary")).await.is_ok());
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()[0].body).unwrap();
+        assert_eq!(body, json!({ "text": "summary" }));
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honored_on_429() {
+        let (url, server) = test_server::serve(vec![(429, vec![("retry-after", "30")], "rate_limited")]).await;
+        let sink = SlackSink::new(&url).unwrap();
+        match sink.send(&test_notification::notice("summary")).await {
+            Err(SinkError::RetryAfter(retry_after)) => assert_eq!(retry_after, Duration::from_secs(30)),
+            res => panic!("unexpected {:?}", res),
+        }
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+}
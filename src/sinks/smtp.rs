@@ -0,0 +1,399 @@
+use crate::types::{OperationError, SmtpSecurity, PositionSide, TimeDisplay};
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::outbox;
+use crate::utils;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Configuration of SMTP digest sink.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    /// Host of SMTP server
+    pub host: String,
+
+    /// Port of SMTP server, `None` for the default of `security`
+    pub port: Option<u16>,
+
+    /// Security of connection
+    pub security: SmtpSecurity,
+
+    /// Username, and password to authenticate with, if any
+    pub credentials: Option<(String, String)>,
+
+    /// Sender
+    pub from: Mailbox,
+
+    /// Recipients
+    pub to: Vec<Mailbox>,
+
+    /// Interval of sending digest
+    pub interval: Duration,
+
+    /// Number of the largest liquidations listed in digest
+    pub top_n: usize,
+
+    /// How times are displayed in digest
+    pub time_display: TimeDisplay,
+}
+
+/// Sink collecting liquidations into a window, then sending a digest of the
+/// window by email periodically rather than an email for each liquidation.
+///
+/// Notification is settled as soon as it is collected, so liquidations in
+/// the current window are not replayed after restart.
+pub struct SmtpDigestSink {
+    window: Arc<Mutex<DigestWindow>>,
+    health: Arc<HealthTracker>,
+}
+
+impl SmtpDigestSink {
+    /// Create a new SMTP digest sink, and spawn the task sending digest
+    /// periodically. It has to be called within tokio runtime.
+    ///
+    /// # Arguments
+    /// * `config` - configuration
+    pub fn new(config: SmtpConfig) -> Result<Self, OperationError> {
+        let builder = match config.security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host),
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpSecurity::Plain => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)),
+        };
+        let mut builder = match builder {
+            Ok(res) => res,
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot set up SMTP transport to {}; err={}", config.host, e),
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        if let Some((username, password)) = &config.credentials {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let window = Arc::new(Mutex::new(DigestWindow::new(outbox::now_ms())));
+        let health = Arc::new(HealthTracker::default());
+        tokio::spawn(run_digest(builder.build(), config, window.clone(), health.clone()));
+        Ok(Self { window, health })
+    }
+
+    /// Create SMTP digest sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_SMTP_HOST`, `HX_BYBIT_SHIPREKT_SMTP_FROM`,
+    /// `HX_BYBIT_SHIPREKT_SMTP_TO`, and optionally
+    /// `HX_BYBIT_SHIPREKT_SMTP_PORT`, `HX_BYBIT_SHIPREKT_SMTP_SECURITY`,
+    /// `HX_BYBIT_SHIPREKT_SMTP_USERNAME`, `HX_BYBIT_SHIPREKT_SMTP_PASSWORD`,
+    /// `HX_BYBIT_SHIPREKT_DIGEST_INTERVAL_SECS`,
+    /// `HX_BYBIT_SHIPREKT_DIGEST_TOP_N`, and those of time display as of
+    /// `utils::time_display_from_env`.
+    ///
+    /// # Returns
+    /// `None` if host is not defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let host = match std::env::var("HX_BYBIT_SHIPREKT_SMTP_HOST") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let from = match std::env::var("HX_BYBIT_SHIPREKT_SMTP_FROM") {
+            Ok(res) => parse_mailbox(&res)?,
+            Err(e) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_SMTP_FROM not defined; err={}", e),
+        };
+        let to = match std::env::var("HX_BYBIT_SHIPREKT_SMTP_TO") {
+            Ok(res) => res.split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(parse_mailbox)
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(e) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_SMTP_TO not defined; err={}", e),
+        };
+        if to.is_empty() {
+            ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_SMTP_TO has no recipient");
+        }
+        let credentials = match (std::env::var("HX_BYBIT_SHIPREKT_SMTP_USERNAME"), std::env::var("HX_BYBIT_SHIPREKT_SMTP_PASSWORD")) {
+            (Ok(username), Ok(password)) if !username.is_empty() => Some((username, password)),
+            _ => None,
+        };
+
+        let config = SmtpConfig {
+            host,
+            // 0 means the default of security
+            port: match utils::get_env_var_or("HX_BYBIT_SHIPREKT_SMTP_PORT", 0_u16)? {
+                0 => None,
+                port => Some(port),
+            },
+            security: utils::get_env_var_or("HX_BYBIT_SHIPREKT_SMTP_SECURITY", SmtpSecurity::StartTls)?,
+            credentials,
+            from,
+            to,
+            interval: Duration::from_secs(utils::get_env_var_or("HX_BYBIT_SHIPREKT_DIGEST_INTERVAL_SECS", 3600_u64)?.max(1)),
+            top_n: utils::get_env_var_or("HX_BYBIT_SHIPREKT_DIGEST_TOP_N", 10)?,
+            time_display: utils::time_display_from_env("SMTP")?,
+        };
+        Ok(Some(Self::new(config)?))
+    }
+}
+
+#[async_trait]
+impl NotificationSink for SmtpDigestSink {
+    fn name(&self) -> &str {
+        "smtp"
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // notification not of a single liquidation e.g. summary of backlog
+        // policy has nothing to add into digest
+        if notification.details.is_some() {
+            lock_window(&self.window).add(notification);
+        }
+        Ok(())
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+}
+
+/// Totals of liquidations of a side.
+#[derive(Debug, Default, Clone)]
+struct SideTotal {
+    count: u64,
+    worth: Decimal,
+}
+
+/// Liquidations collected in a window of digest.
+#[derive(Debug, Clone)]
+struct DigestWindow {
+    /// Timestamp in milliseconds when window started
+    started_at_ms: u64,
+    long: SideTotal,
+    short: SideTotal,
+
+    /// Largest liquidations so far; worth, summary, and timestamp in
+    /// milliseconds sorted by worth descending
+    top: Vec<(Decimal, String, u64)>,
+}
+
+impl DigestWindow {
+    fn new(started_at_ms: u64) -> Self {
+        Self { started_at_ms, long: SideTotal::default(), short: SideTotal::default(), top: Vec::new() }
+    }
+
+    fn count(&self) -> u64 {
+        self.long.count + self.short.count
+    }
+
+    fn add(&mut self, notification: &Notification) {
+        let details = match &notification.details {
+            Some(details) => details,
+            None => return,
+        };
+        let total = if details.position_side() == PositionSide::Long { &mut self.long } else { &mut self.short };
+        total.count += 1;
+        total.worth += notification.worth;
+        self.top.push((notification.worth, notification.summary.clone(), details.event_time_ms));
+    }
+
+    /// Keep only the largest `n` liquidations.
+    fn truncate_top(&mut self, n: usize) {
+        self.top.sort_by_key(|entry| std::cmp::Reverse(entry.0));
+        self.top.truncate(n);
+    }
+
+    /// Merge window which failed to be sent back into this (newer) one, so
+    /// it is part of the next digest.
+    fn merge_older(&mut self, older: DigestWindow) {
+        self.started_at_ms = self.started_at_ms.min(older.started_at_ms);
+        self.long.count += older.long.count;
+        self.long.worth += older.long.worth;
+        self.short.count += older.short.count;
+        self.short.worth += older.short.worth;
+        self.top.extend(older.top);
+    }
+}
+
+/// Lock window, recovering it even if another thread panicked while holding
+/// the lock as window is always left consistent.
+fn lock_window(window: &Mutex<DigestWindow>) -> std::sync::MutexGuard<'_, DigestWindow> {
+    window.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Periodically send digest of the window, then start a new one.
+async fn run_digest(transport: AsyncSmtpTransport<Tokio1Executor>, config: SmtpConfig, window: Arc<Mutex<DigestWindow>>, health: Arc<HealthTracker>) {
+    let mut interval = tokio::time::interval(config.interval);
+    // skip the immediate first tick, window just started
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+
+        let now_ms = outbox::now_ms();
+        let digest = match take_digest(&window, config.top_n, now_ms) {
+            Some(res) => res,
+            None => continue,
+        };
+
+        let res = send_digest(&transport, &config, &digest, now_ms).await;
+        match health.record(res) {
+            Ok(_) => println!("[smtp] Sent digest of {} liquidation(s)", digest.count()),
+            Err(e) => {
+                eprintln!("[smtp] cannot send digest, it will be part of the next one; err={}", e);
+                lock_window(&window).merge_older(digest);
+            },
+        }
+    }
+}
+
+/// Take digest of the window which just ended, starting a new window.
+///
+/// # Arguments
+/// * `window` - current window
+/// * `top_n` - number of the largest liquidations listed in digest
+/// * `now_ms` - timestamp in milliseconds when window ended
+///
+/// # Returns
+/// `None` if nothing is collected in the window.
+fn take_digest(window: &Mutex<DigestWindow>, top_n: usize, now_ms: u64) -> Option<DigestWindow> {
+    let mut digest = std::mem::replace(&mut *lock_window(window), DigestWindow::new(now_ms));
+    if digest.count() == 0 {
+        return None;
+    }
+    digest.truncate_top(top_n);
+    Some(digest)
+}
+
+/// Form subject, and body of email of digest.
+///
+/// # Arguments
+/// * `digest` - digest of window
+/// * `ended_at_ms` - timestamp in milliseconds when window ended
+/// * `time_display` - how times are displayed
+fn digest_email(digest: &DigestWindow, ended_at_ms: u64, time_display: &TimeDisplay) -> (String, String) {
+    let total_worth = digest.long.worth + digest.short.worth;
+    let subject = format!("Bybit shiprekt digest: {} liquidations worth ${}", digest.count(), utils::format_worth(total_worth));
+
+    let mut body = format!("Bybit shiprekt digest from {} to {}\n\n", time_display.format_ms(digest.started_at_ms), time_display.format_ms(ended_at_ms));
+    body.push_str(&format!("Liquidations: {} worth ${}\n", digest.count(), utils::format_worth(total_worth)));
+    body.push_str(&format!("  Long:  {} worth ${}\n", digest.long.count, utils::format_worth(digest.long.worth)));
+    body.push_str(&format!("  Short: {} worth ${}\n", digest.short.count, utils::format_worth(digest.short.worth)));
+    if !digest.top.is_empty() {
+        body.push_str(&format!("\nTop {} liquidations:\n", digest.top.len()));
+        for (i, (_, summary, time_ms)) in digest.top.iter().enumerate() {
+            body.push_str(&format!("{:>3}. {} - {}\n", i + 1, summary, time_display.format_ms(*time_ms)));
+        }
+    }
+    (subject, body)
+}
+
+/// Build, and send email of digest.
+async fn send_digest(transport: &AsyncSmtpTransport<Tokio1Executor>, config: &SmtpConfig, digest: &DigestWindow, ended_at_ms: u64) -> Result<(), SinkError> {
+    let (subject, body) = digest_email(digest, ended_at_ms, &config.time_display);
+
+    let mut builder = Message::builder()
+        .from(config.from.clone())
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for to in config.to.iter() {
+        builder = builder.to(to.clone());
+    }
+    let message = match builder.body(body) {
+        Ok(res) => res,
+        Err(e) => return Err(SinkError::Permanent(format!("cannot build email; err={}", e))),
+    };
+
+    match transport.send(message).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.is_permanent() => Err(SinkError::Permanent(e.to_string())),
+        Err(e) => Err(SinkError::Transient(e.to_string())),
+    }
+}
+
+/// Parse mailbox e.g. `bot@example.com`, or `Shiprekt <bot@example.com>`.
+fn parse_mailbox(s: &str) -> Result<Mailbox, OperationError> {
+    match s.trim().parse::<Mailbox>() {
+        Ok(res) => Ok(res),
+        Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "invalid email address '{}'; err={}", s, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    fn time_display() -> TimeDisplay {
+        TimeDisplay::new(chrono_tz::Tz::UTC, utils::DEFAULT_TIME_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn liquidations_are_totaled_by_side_and_notices_are_left_out() {
+        let mut window = DigestWindow::new(1_700_000_000_000);
+        window.add(&test_notification::liquidation("BTCUSDT", Side::Buy, "64000", "0.5"));
+        window.add(&test_notification::liquidation("ETHUSDT", Side::Buy, "3000", "2"));
+        window.add(&test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.1"));
+        window.add(&test_notification::notice("summary"));
+        assert_eq!(window.count(), 3);
+        assert_eq!((window.long.count, window.long.worth), (2, Decimal::from(38000)));
+        assert_eq!((window.short.count, window.short.worth), (1, Decimal::from(6400)));
+    }
+
+    #[test]
+    fn window_is_flushed_with_its_largest_once_it_ends() {
+        let window = Mutex::new(DigestWindow::new(1_700_000_000_000));
+        for (price, qty) in [("64000", "0.1"), ("64000", "0.5"), ("64000", "0.2")] {
+            lock_window(&window).add(&test_notification::liquidation("BTCUSDT", Side::Buy, price, qty));
+        }
+
+        let digest = take_digest(&window, 2, 1_700_000_060_000).unwrap();
+        assert_eq!(digest.count(), 3);
+        assert_eq!(digest.top.iter().map(|entry| entry.0).collect::<Vec<_>>(), vec![Decimal::from(32000), Decimal::from(12800)]);
+        // the next window starts as this one ends, and nothing is to be sent
+        // until something is collected into it
+        assert_eq!(lock_window(&window).started_at_ms, 1_700_000_060_000);
+        assert!(take_digest(&window, 2, 1_700_000_120_000).is_none());
+        assert_eq!(lock_window(&window).started_at_ms, 1_700_000_120_000);
+    }
+
+    #[test]
+    fn digest_failed_to_be_sent_is_part_of_the_next_one() {
+        let window = Mutex::new(DigestWindow::new(1_700_000_000_000));
+        lock_window(&window).add(&test_notification::liquidation("BTCUSDT", Side::Buy, "64000", "0.5"));
+        let failed = take_digest(&window, 10, 1_700_000_060_000).unwrap();
+        lock_window(&window).add(&test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.1"));
+        lock_window(&window).merge_older(failed);
+
+        let digest = take_digest(&window, 10, 1_700_000_120_000).unwrap();
+        assert_eq!(digest.started_at_ms, 1_700_000_000_000);
+        assert_eq!((digest.long.count, digest.short.count), (1, 1));
+        assert_eq!(digest.top.len(), 2);
+    }
+
+    #[test]
+    fn email_lists_totals_and_the_largest() {
+        let largest = test_notification::liquidation("BTCUSDT", Side::Buy, "64000", "0.5");
+        let smaller = test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.1");
+        let window = Mutex::new(DigestWindow::new(1_700_000_000_000));
+        lock_window(&window).add(&smaller);
+        lock_window(&window).add(&largest);
+        let digest = take_digest(&window, 10, 1_700_000_060_000).unwrap();
+
+        let (subject, body) = digest_email(&digest, 1_700_000_060_000, &time_display());
+        assert_eq!(subject, "Bybit shiprekt digest: 2 liquidations worth $38,400");
+        assert_eq!(body, format!(concat!(
+            "Bybit shiprekt digest from 2023-11-14 22:13:20.000 UTC to 2023-11-14 22:14:20.000 UTC\n",
+            "\n",
+            "Liquidations: 2 worth $38,400\n",
+            "  Long:  1 worth $32,000\n",
+            "  Short: 1 worth $6,400\n",
+            "\n",
+            "Top 2 liquidations:\n",
+            "  1. {} - 2023-11-14 22:13:20.123 UTC\n",
+            "  2. {} - 2023-11-14 22:13:20.123 UTC\n"),
+            largest.summary, smaller.summary));
+    }
+}
@@ -0,0 +1,102 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+
+use async_trait::async_trait;
+use regex::Regex;
+use rustelebot::{create_instance, send_message_async};
+use rustelebot::types::BotInstance;
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Maximum length of a telegram message in characters
+const TELEGRAM_MAX_MESSAGE_LEN: usize = 4096;
+
+/// Seconds to retry after as told in description of 429 error
+static RETRY_AFTER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Sink sending notifications as plain text messages to a telegram chat.
+pub struct TelegramSink {
+    bot_instance: BotInstance,
+    chat_id: String,
+    health: HealthTracker,
+}
+
+impl TelegramSink {
+    /// Create a new telegram sink.
+    ///
+    /// # Arguments
+    /// * `bot_token` - telegram bot token
+    /// * `chat_id` - chat id of channel to send messages to
+    pub fn new(bot_token: &str, chat_id: &str) -> Self {
+        Self { bot_instance: create_instance(bot_token, chat_id), chat_id: chat_id.to_owned(), health: HealthTracker::default() }
+    }
+
+    /// Create telegram sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_TELEGRAM_BOT_TOKEN`, and
+    /// `HX_BYBIT_SHIPREKT_TELEGRAM_CHANNEL_CHAT_ID`.
+    ///
+    /// # Returns
+    /// `None` if neither is defined. `Err` with
+    /// `OperationError::ErrorMissingRequiredEnvVar` if only one of them is
+    /// defined.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let bot_token = std::env::var("HX_BYBIT_SHIPREKT_TELEGRAM_BOT_TOKEN").ok().filter(|v| !v.is_empty());
+        let chat_id = std::env::var("HX_BYBIT_SHIPREKT_TELEGRAM_CHANNEL_CHAT_ID").ok().filter(|v| !v.is_empty());
+        match (bot_token, chat_id) {
+            (Some(bot_token), Some(chat_id)) => Ok(Some(Self::new(&bot_token, &chat_id))),
+            (None, None) => Ok(None),
+            (Some(_), None) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_TELEGRAM_CHANNEL_CHAT_ID not defined"),
+            (None, Some(_)) => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_TELEGRAM_BOT_TOKEN not defined"),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for TelegramSink {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    fn destination(&self) -> &str {
+        &self.chat_id
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        // FIXME: upstream fix for rustelebot for `Display` of `ErrorResult`
+        let res = send_message_async(&self.bot_instance, &notification.text).await
+            .map_err(|e| classify_telegram_error(&e.msg));
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    fn max_message_len(&self) -> usize {
+        TELEGRAM_MAX_MESSAGE_LEN
+    }
+}
+
+/// Classify error message as returned from sending a message to telegram.
+///
+/// # Arguments
+/// * `msg` - error message
+fn classify_telegram_error(msg: &str) -> SinkError {
+    // telegram's description of 429 is "Too Many Requests: retry after <secs>"
+    let regex = RETRY_AFTER_REGEX.get_or_init(|| Regex::new(r"(?i)retry after (\d+)").unwrap());
+    if let Some(caps) = regex.captures(msg) {
+        if let Ok(secs) = caps[1].parse::<u64>() {
+            return SinkError::RetryAfter(Duration::from_secs(secs));
+        }
+    }
+
+    let permanent_prefixes = ["Bad Request", "Unauthorized", "Forbidden", "Not Found"];
+    if permanent_prefixes.iter().any(|p| msg.starts_with(p)) {
+        SinkError::Permanent(msg.to_owned())
+    }
+    else {
+        SinkError::Transient(msg.to_owned())
+    }
+}
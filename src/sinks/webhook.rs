@@ -0,0 +1,182 @@
+use crate::types::OperationError;
+use crate::delivery::Notification;
+use crate::sinks::{NotificationSink, SinkError, SinkHealth, HealthTracker};
+use crate::sinks::{http, event};
+use crate::sinks::event::LiquidationEvent;
+use crate::sinks::deadletter::DeadLetterFile;
+use crate::outbox;
+use crate::utils;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use isahc::HttpClient;
+use serde_json::json;
+use sha2::Sha256;
+
+use std::time::Duration;
+
+/// Timeout of each request to webhook
+const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default path of dead-letter file of webhook
+const DEFAULT_DEAD_LETTER_PATH: &str = "bybit-shiprekt-webhook-dead-letter.jsonl";
+
+/// Header carrying signature of request
+const SIGNATURE_HEADER: &str = "x-shiprekt-signature";
+
+/// Header carrying timestamp in seconds which is part of signed content
+const TIMESTAMP_HEADER: &str = "x-shiprekt-timestamp";
+
+/// Sink posting normalized liquidation events as JSON to an HTTP endpoint,
+/// signed with HMAC-SHA256.
+///
+/// Signature is `sha256=<hex>` in `X-Shiprekt-Signature` header computed over
+/// `<timestamp>.<body>` where timestamp is the value of `X-Shiprekt-Timestamp`
+/// header, so receiver can reject replayed requests.
+pub struct WebhookSink {
+    client: HttpClient,
+    url: String,
+    secret: Vec<u8>,
+    health: HealthTracker,
+
+    /// File to append events given up to
+    dead_letter: DeadLetterFile,
+}
+
+impl WebhookSink {
+    /// Create a new webhook sink.
+    ///
+    /// # Arguments
+    /// * `url` - URL to post events to
+    /// * `secret` - secret key to sign requests with
+    /// * `dead_letter` - file to append events given up to
+    pub fn new(url: &str, secret: &str, dead_letter: DeadLetterFile) -> Result<Self, OperationError> {
+        Ok(Self {
+            client: http::create_client(WEBHOOK_REQUEST_TIMEOUT)?,
+            url: url.to_owned(),
+            secret: secret.as_bytes().to_vec(),
+            health: HealthTracker::default(),
+            dead_letter,
+        })
+    }
+
+    /// Create webhook sink from environment variables
+    /// `HX_BYBIT_SHIPREKT_WEBHOOK_URL`, `HX_BYBIT_SHIPREKT_WEBHOOK_SECRET`,
+    /// and optionally `HX_BYBIT_SHIPREKT_WEBHOOK_DEAD_LETTER_PATH`.
+    ///
+    /// # Returns
+    /// `None` if URL is not defined. `Err` with
+    /// `OperationError::ErrorMissingRequiredEnvVar` if it is, but secret is
+    /// not.
+    pub fn from_env() -> Result<Option<Self>, OperationError> {
+        let url = match std::env::var("HX_BYBIT_SHIPREKT_WEBHOOK_URL") {
+            Ok(res) if !res.is_empty() => res,
+            _ => return Ok(None),
+        };
+        let secret = match std::env::var("HX_BYBIT_SHIPREKT_WEBHOOK_SECRET") {
+            Ok(res) if !res.is_empty() => res,
+            _ => ret_err!(OperationError::ErrorMissingRequiredEnvVar, "HX_BYBIT_SHIPREKT_WEBHOOK_SECRET not defined"),
+        };
+        Ok(Some(Self::new(&url, &secret, DeadLetterFile::from_env("WEBHOOK", DEFAULT_DEAD_LETTER_PATH))?))
+    }
+
+    /// Sign the body with the timestamp.
+    ///
+    /// # Returns
+    /// Value of signature header.
+    fn sign(&self, timestamp: u64, body: &str) -> String {
+        // HMAC accepts key of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body.as_bytes());
+        format!("sha256={}", utils::to_hex(&mac.finalize().into_bytes()))
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn is_lossless(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), SinkError> {
+        let body = document(notification).to_string();
+        let timestamp = outbox::now_ms() / 1000;
+        let signature = self.sign(timestamp, &body);
+        let timestamp_str = timestamp.to_string();
+        let headers = [(SIGNATURE_HEADER, signature.as_str()), (TIMESTAMP_HEADER, timestamp_str.as_str())];
+
+        let res = match http::send(&self.client, "POST", &self.url, "application/json", body.into_bytes(), &headers).await {
+            Ok(response) if response.is_success() => Ok(()),
+            Ok(response) => Err(response.to_sink_error()),
+            Err(e) => Err(e),
+        };
+        self.health.record(res)
+    }
+
+    async fn health(&self) -> SinkHealth {
+        self.health.health()
+    }
+
+    async fn dead_letter(&self, notification: &Notification, reason: &str) {
+        self.dead_letter.append(self.name(), reason, document(notification)).await;
+    }
+}
+
+/// Form the JSON document to post for the notification.
+/// Notification not of a single liquidation e.g. summary as per backlog
+/// policy is posted as a `notice` with its text.
+fn document(notification: &Notification) -> serde_json::Value {
+    match LiquidationEvent::from_notification(notification) {
+        Some(event) => json!({ "type": "liquidation", "event": event }),
+        None => json!({
+            "type": "notice",
+            "version": event::EVENT_SCHEMA_VERSION,
+            "text": notification.text,
+            "worth": notification.worth,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::http::test_server;
+    use crate::sinks::test_notification;
+    use crate::types::Side;
+
+    /// Get value of header in head of request.
+    fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+        head.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+
+    #[tokio::test]
+    async fn signature_is_over_timestamp_and_body_as_received() {
+        let (url, server) = test_server::serve(vec![(204, vec![], "")]).await;
+        let sink = WebhookSink::new(&url, "secret", DeadLetterFile::new(None)).unwrap();
+        assert!(sink.send(&test_notification::liquidation("BTCUSDT", Side::Sell, "64000", "0.5")).await.is_ok());
+
+        let received = server.await.unwrap();
+        let timestamp = header(&received[0].head, "x-shiprekt-timestamp").unwrap();
+        let timestamp_s: u64 = timestamp.parse().unwrap();
+        assert!(timestamp_s.abs_diff(outbox::now_ms() / 1000) <= 5);
+
+        // as receiver verifies it
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(format!("{}.{}", timestamp, received[0].body).as_bytes());
+        let expected = format!("sha256={}", utils::to_hex(&mac.finalize().into_bytes()));
+        assert_eq!(header(&received[0].head, "x-shiprekt-signature"), Some(expected.as_str()));
+
+        let body: serde_json::Value = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(body["type"], "liquidation");
+        assert_eq!(body["event"]["symbol"], "BTCUSDT");
+    }
+}
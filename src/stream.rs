@@ -0,0 +1,360 @@
+use crate::types::*;
+use crate::utils;
+use crate::protocol;
+use crate::liquidation::Liquidation;
+use crate::supervisor::{Supervisor, ConnectionState};
+use crate::watchdog::Watchdog;
+
+use tungstenite::Message;
+use tungstenite::error::Error as TungsError;
+use futures_util::stream::{StreamExt, SplitSink, SplitStream};
+use futures_util::sink::SinkExt;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+use tokio_tungstenite::{WebSocketStream, MaybeTlsStream};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Connect to the specified endpoint, subscribe to its topics then forward
+/// all liquidations received into `liquidation_tx`.
+/// Connection failure, stream end, and close frame are all treated the same;
+/// supervisor backs off then it connects again.
+/// Topics added to `topics_rx` while streaming e.g. of newly listed
+/// instruments are subscribed to on the same connection. Those removed stay
+/// subscribed until the next connection.
+///
+/// # Arguments
+/// * `endpoint` - endpoint to connect to
+/// * `topics_rx` - latest topics of the endpoint; its topics as of each
+///   connection take place of those of `endpoint`
+/// * `liquidation_tx` - sending end of the shared liquidation pipeline
+/// * `supervisor` - supervisor of this endpoint's connection
+/// * `config` - configuration of streaming session
+///
+/// # Returns
+/// Return only when supervisor gives up reconnecting, or the receiving end of
+/// the pipeline is gone.
+pub async fn run_endpoint(mut endpoint: Endpoint, mut topics_rx: watch::Receiver<Vec<String>>, liquidation_tx: Sender<Liquidation>, mut supervisor: Supervisor, config: StreamConfig) -> Result<(), OperationError> {
+    let name = endpoint.name;
+
+    loop {
+        endpoint.topics = topics_rx.borrow_and_update().clone();
+        let subscribe_requests = protocol::subscribe_requests(&endpoint, &endpoint.topics, 0);
+
+        supervisor.set_state(ConnectionState::Connecting);
+        let (ws_stream, _response) = match utils::connect_async_to_wss(endpoint.url).await {
+            Ok(res) => res,
+            Err(e) => {
+                eprintln!("[{}] {}", name, e);
+                supervisor.record_connect_failure();
+                supervisor.backoff().await?;
+                continue;
+            }
+        };
+
+        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+        supervisor.set_state(ConnectionState::Subscribing);
+        match subscribe(&endpoint, &subscribe_requests, &mut ws_sender, &mut ws_receiver, &liquidation_tx, &config).await {
+            Ok(Some(conn_id)) => println!("[{}] subscribed to {} topics; conn_id={}", name, endpoint.topics.len(), conn_id),
+            Ok(None) => {
+                supervisor.backoff().await?;
+                continue;
+            },
+            Err(e @ OperationError::ErrorWssTopicSubscription(_)) => {
+                if config.subscribe_failure_policy == SubscribeFailurePolicy::Abort {
+                    supervisor.set_state(ConnectionState::Stopped);
+                    return Err(e);
+                }
+                eprintln!("[{}] {}", name, e);
+                supervisor.backoff().await?;
+                continue;
+            },
+            Err(e) => return Err(e),
+        }
+
+        supervisor.set_state(ConnectionState::Streaming);
+        run_session(&endpoint, subscribe_requests.len(), &mut topics_rx, ws_sender, ws_receiver, &liquidation_tx, &supervisor, &config).await?;
+        supervisor.backoff().await?;
+    }
+}
+
+/// Send subscription requests then wait for all of their acknowledgements.
+/// Liquidation data arrived in between is forwarded to the pipeline as usual.
+///
+/// # Arguments
+/// * `endpoint` - endpoint connected to
+/// * `requests` - subscription requests to send
+/// * `ws_sender` - sending half of websocket
+/// * `ws_receiver` - receiving half of websocket
+/// * `liquidation_tx` - sending end of the shared liquidation pipeline
+/// * `config` - configuration of streaming session
+///
+/// # Returns
+/// * `Ok(Some(conn_id))` - all requests are acknowledged
+/// * `Ok(None)` - timed out, or connection ended; should be re-established
+/// * `Err(OperationError::ErrorWssTopicSubscription)` - request is rejected
+///   with the exchange's message
+/// * `Err(OperationError::ErrorInternalSyncCommunication)` - receiving end of
+///   the pipeline is gone
+async fn subscribe(endpoint: &Endpoint, requests: &[SubscribeRequest], ws_sender: &mut SplitSink<WsStream, Message>, ws_receiver: &mut SplitStream<WsStream>, liquidation_tx: &Sender<Liquidation>, config: &StreamConfig) -> Result<Option<String>, OperationError> {
+    let name = endpoint.name;
+    if requests.is_empty() {
+        eprintln!("[{}] Warning: no topic to subscribe to", name);
+        return Ok(Some("-".to_owned()));
+    }
+
+    for request in requests {
+        if let Err(e) = ws_sender.send(Message::Text(request.message.clone())).await {
+            eprintln!("[{}] error sending subscription request {}; err={}", name, request.req_id, e);
+            return Ok(None);
+        }
+    }
+
+    let mut pending: Vec<&SubscribeRequest> = requests.iter().collect();
+    let mut conn_id = String::new();
+    let deadline = Instant::now() + config.subscribe_timeout;
+
+    while !pending.is_empty() {
+        let msg = match tokio::time::timeout_at(deadline, ws_receiver.next()).await {
+            Ok(Some(Ok(msg))) => msg,
+            Ok(Some(Err(e))) => {
+                eprintln!("[{}] Error: while subscribing; err={}", name, e);
+                return Ok(None);
+            },
+            Ok(None) => {
+                eprintln!("[{}] Error: stream ended while subscribing", name);
+                return Ok(None);
+            },
+            Err(_) => {
+                let pending_ids: Vec<&str> = pending.iter().map(|r| r.req_id.as_str()).collect();
+                eprintln!("[{}] Error: timed out waiting for subscription acknowledgement of {:?}", name, pending_ids);
+                return Ok(None);
+            },
+        };
+
+        let json_str = match msg {
+            Message::Text(json_str) => json_str,
+            Message::Close(_) => {
+                println!("[{}] (websocket closed while subscribing)", name);
+                return Ok(None);
+            },
+            _ => continue,
+        };
+
+        match protocol::parse_message(endpoint.protocol, &json_str) {
+            Ok(StreamMessage::Response(response)) => {
+                let position = match pending.iter().position(|r| protocol::is_subscribe_ack(&response, r)) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                let request = pending.remove(position);
+                if !response.success {
+                    ret_err!(OperationError::ErrorWssTopicSubscription, "subscription request {} for {:?} rejected; ret_msg={}; conn_id={}",
+                             request.req_id,
+                             request.args,
+                             response.ret_msg.unwrap_or_default(),
+                             response.conn_id);
+                }
+                conn_id = response.conn_id;
+            },
+            Ok(StreamMessage::Liquidations(liquidations)) => {
+                for liquidation in liquidations {
+                    if let Err(e) = liquidation_tx.send(liquidation).await {
+                        ret_err!(OperationError::ErrorInternalSyncCommunication, "liquidation pipeline closed; err={}", e);
+                    }
+                }
+            },
+            Err(e) => eprintln!("[{}] -- error parsing JSON response: {} --", name, e),
+        }
+    }
+
+    Ok(Some(conn_id))
+}
+
+/// Stream from connected websocket until the connection ends.
+///
+/// # Arguments
+/// * `endpoint` - endpoint connected to, and subscribed to its topics
+/// * `requests_sent` - number of subscription requests sent so far
+/// * `topics_rx` - latest topics of the endpoint
+/// * `ws_sender` - sending half of websocket
+/// * `ws_receiver` - receiving half of websocket
+/// * `liquidation_tx` - sending end of the shared liquidation pipeline
+/// * `supervisor` - supervisor of this endpoint's connection
+/// * `config` - configuration of streaming session
+///
+/// # Returns
+/// `Ok` when connection ends, or is declared dead by liveness watchdog, and
+/// should be re-established, or `Err` when the receiving end of the pipeline
+/// is gone.
+#[allow(clippy::too_many_arguments)]
+async fn run_session(endpoint: &Endpoint, mut requests_sent: usize, topics_rx: &mut watch::Receiver<Vec<String>>, mut ws_sender: SplitSink<WsStream, Message>, mut ws_receiver: SplitStream<WsStream>, liquidation_tx: &Sender<Liquidation>, supervisor: &Supervisor, config: &StreamConfig) -> Result<(), OperationError> {
+    let name = endpoint.name;
+    let mut subscribed = endpoint.topics.clone();
+    let mut heartbeat_interval = tokio::time::interval(config.heartbeat_interval);
+    let mut watchdog = Watchdog::new(config.liveness_timeout, Instant::now());
+
+    loop {
+        tokio::select! {
+            msg_item = ws_receiver.next() => {
+                let msg = match msg_item {
+                    Some(msg) => msg,
+                    None => {
+                        eprintln!("[{}] Error: stream ended", name);
+                        return Ok(());
+                    }
+                };
+
+                match msg {
+                    Ok(Message::Text(json_str)) => {
+                        match protocol::parse_message(endpoint.protocol, &json_str) {
+                            Ok(StreamMessage::Response(response)) => {
+                                if response.op.eq_ignore_ascii_case("ping") {
+                                    if let Some(rtt) = watchdog.on_pong(Instant::now()) {
+                                        supervisor.record_rtt(rtt);
+                                    }
+                                }
+
+                                // late, or unsolicited rejection still needs to be known
+                                if !response.success {
+                                    eprintln!("[{}] Error: {} request failed; ret_msg={}; conn_id={}", name, response.op, response.ret_msg.unwrap_or_default(), response.conn_id);
+                                }
+                            },
+                            Ok(StreamMessage::Liquidations(liquidations)) => {
+                                watchdog.on_data(Instant::now());
+                                for liquidation in liquidations {
+                                    // the only case this fails is the receiving end is gone,
+                                    // thus there is no point in keep receiving
+                                    if let Err(e) = liquidation_tx.send(liquidation).await {
+                                        ret_err!(OperationError::ErrorInternalSyncCommunication, "liquidation pipeline closed; err={}", e);
+                                    }
+                                }
+                            },
+                            Err(e) => eprintln!("[{}] -- error parsing JSON response: {} --", name, e),
+                        }
+                    },
+                    Ok(Message::Ping(msg)) => println!("[{}] Received ping message; msg={:#?}", name, msg),
+                    Ok(Message::Pong(msg)) => {
+                        watchdog.on_pong(Instant::now());
+                        println!("[{}] Received pong message; msg={:#?}", name, msg);
+                    },
+                    Ok(Message::Binary(bins)) => println!("[{}] Received Binbary message, content={}", name, std::str::from_utf8(&bins).unwrap_or("unknown")),
+                    Ok(Message::Frame(frame)) => println!("[{}] Received Frame message, content={:?}", name, frame),
+                    Ok(Message::Close(_)) => {
+                        println!("[{}] (websocket closed)", name);
+                        return Ok(());      // reconnect to websocket again
+                    },
+
+                    // from now they are error cases that we need to
+                    // reconnect to websocket if occur
+                    //
+                    // by break into the outer loop
+                    Err(TungsError::ConnectionClosed) => {
+                        eprintln!("[{}] Error: connection closed", name);
+                        return Ok(());
+                    },
+                    Err(TungsError::AlreadyClosed) => {
+                        eprintln!("[{}] Error: already closed", name);
+                        return Ok(());
+                    },
+                    Err(TungsError::Io(e)) => {
+                        eprintln!("[{}] Error: IO; err={}", name, e);
+                        return Ok(());
+                    },
+                    Err(TungsError::Tls(e)) => {
+                        eprintln!("[{}] Error:: Tls error; err={}", name, e);
+                        return Ok(());
+                    },
+                    Err(TungsError::Capacity(e)) => {
+                        type CError = tungstenite::error::CapacityError;
+                        match e {
+                            CError::TooManyHeaders => eprintln!("[{}] Error: CapacityError, too many headers", name),
+                            CError::MessageTooLong{ size, max_size } => eprintln!("[{}] Error: CapacityError, message too long with size={}, max_size={}", name, size, max_size),
+                        }
+                        return Ok(());
+                    },
+                    Err(TungsError::Protocol(e)) => {
+                        eprintln!("[{}] Error: Protocol, err={}", name, e);
+                        return Ok(());
+                    },
+                    Err(TungsError::SendQueueFull(e)) => {
+                        type PMsg = tungstenite::protocol::Message;
+
+                        match e {
+                            PMsg::Text(text) => eprintln!("[{}] Error: SendQueueFull for Text message, content={}", name, text),
+                            PMsg::Binary(bins) => eprintln!("[{}] Error: SendQueueFull for Binary message, content={}", name, std::str::from_utf8(&bins).unwrap_or("unknown")),
+                            PMsg::Ping(bins) => eprintln!("[{}] Error: SendQueueFull for Ping message, content={}", name, std::str::from_utf8(&bins).unwrap_or("unknown")),
+                            PMsg::Pong(bins) => eprintln!("[{}] Error: SendQueueFull for Pong message, content={}", name, std::str::from_utf8(&bins).unwrap_or("unknown")),
+                            PMsg::Close(close_frame_optional) => {
+                                match close_frame_optional {
+                                    Some(close_frame) => eprintln!("[{}] Error: SendQueueFull for Close message, content={:?}", name, close_frame),
+                                    None => eprintln!("[{}] Error: SendQueueFull for Close message, no close-frame content", name)
+                                }
+                            },
+                            PMsg::Frame(frame) => eprintln!("[{}] Error: SendQueueFull for Frame messasge, content={:?}", name, frame)
+                        }
+                        return Ok(());
+                    },
+                    Err(TungsError::Utf8) => {
+                        eprintln!("[{}] Error: Utf8 coding error", name);
+                        return Ok(());
+                    },
+                    Err(TungsError::Url(e)) => {
+                        eprintln!("[{}] Error: Invalid Url; err={:?}", name, e);
+                        return Ok(());
+                    },
+                    Err(TungsError::Http(e)) => {
+                        eprintln!("[{}] Error: Http error; err={:?}", name, e);
+                        return Ok(());
+                    },
+                    Err(TungsError::HttpFormat(e)) => {
+                        eprintln!("[{}] Error: Http format error; err{:?}", name, e);
+                        return Ok(());
+                    },
+                }
+            }
+            // NOTE: even heartbeat won't save us from arbitrary connection
+            // closing down (around ~12-14 hours of long running process from
+            // testing), thus watchdog below is there to detect it.
+            _ = heartbeat_interval.tick() => {
+                match ws_sender.send(Message::Text(r#"{"op":"ping"}"#.into())).await {
+                    Ok(_) => {
+                        watchdog.on_ping_sent(Instant::now());
+                        println!("[{}] send ping message (last rtt={}ms)", name, supervisor.last_rtt_ms());
+                    },
+                    Err(e) => eprintln!("[{}] error sending ping message; err={}", name, e),
+                }
+            }
+            // acknowledgement is only checked for rejection as with any other
+            // response
+            Ok(()) = topics_rx.changed() => {
+                let added: Vec<String> = topics_rx.borrow_and_update().iter()
+                    .filter(|topic| !subscribed.contains(topic))
+                    .cloned()
+                    .collect();
+                if added.is_empty() {
+                    continue;
+                }
+                println!("[{}] subscribing to {} more topics", name, added.len());
+                for request in protocol::subscribe_requests(endpoint, &added, requests_sent) {
+                    requests_sent += 1;
+                    if let Err(e) = ws_sender.send(Message::Text(request.message)).await {
+                        eprintln!("[{}] error sending subscription request {}; err={}", name, request.req_id, e);
+                        return Ok(());
+                    }
+                }
+                subscribed.extend(added);
+            }
+            // connection is silently dead, reconnect the same way as if it
+            // was closed
+            _ = tokio::time::sleep_until(watchdog.deadline()) => {
+                eprintln!("[{}] Error: no pong, nor data for {:?}; connection is considered dead", name, watchdog.silence(Instant::now()));
+                supervisor.record_liveness_timeout();
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::types::OperationError;
+
+use rand::Rng;
+use tokio::sync::watch;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// State of a supervised connection.
+///
+/// Transitions are
+/// `Connecting` -> `Subscribing` -> `Streaming` -> `Backoff` -> `Connecting` ...
+/// with any failure on the way jumping straight to `Backoff`, and `Stopped`
+/// once retry policy is exhausted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Establishing websocket connection
+    Connecting,
+
+    /// Connected, sending subscription request(s)
+    Subscribing,
+
+    /// Subscribed, receiving data
+    Streaming,
+
+    /// Waiting before the next connection attempt
+    Backoff {
+        /// Consecutive failed attempt number, starting from 1
+        attempt: u32,
+
+        /// Delay before next connection attempt
+        delay: Duration,
+    },
+
+    /// Gave up connecting as retry policy is exhausted
+    Stopped,
+}
+
+/// Exponential backoff policy with jitter.
+#[derive(Debug, Clone)]
+pub struct BackoffPolicy {
+    /// Delay of the first retry
+    pub initial_delay: Duration,
+
+    /// Cap of delay no matter how many retries
+    pub max_delay: Duration,
+
+    /// Multiplier applied to delay on each consecutive retry
+    pub multiplier: f64,
+
+    /// Maximum number of consecutive retries before giving up, `None` for
+    /// retrying forever
+    pub max_retries: Option<u32>,
+}
+
+impl BackoffPolicy {
+    /// Compute delay of the specified consecutive attempt.
+    /// Half of the delay is fixed, another half is random to avoid all
+    /// connections hammering the server at the same time.
+    ///
+    /// # Arguments
+    /// * `attempt` - consecutive failed attempt number, starting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self.multiplier.powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let base = (self.initial_delay.as_secs_f64() * exp).min(self.max_delay.as_secs_f64());
+        let half = base / 2.0;
+        Duration::from_secs_f64(half + rand::thread_rng().gen_range(0.0..=half))
+    }
+}
+
+/// Counters of a supervised connection.
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    /// Number of successful connections made
+    pub connects: AtomicU64,
+
+    /// Number of failed connection attempts
+    pub connect_failures: AtomicU64,
+
+    /// Number of times it went into backoff to reconnect
+    pub reconnects: AtomicU64,
+
+    /// Number of times connection was declared dead by liveness watchdog
+    pub liveness_timeouts: AtomicU64,
+
+    /// Last measured round-trip time of ping/pong in milliseconds
+    pub last_rtt_ms: AtomicU64,
+}
+
+/// Supervisor of a single connection.
+/// It tracks state of connection, and applies backoff policy between
+/// connection attempts.
+pub struct Supervisor {
+    policy: BackoffPolicy,
+    attempt: u32,
+    state_tx: watch::Sender<ConnectionState>,
+    stats: Arc<ConnectionStats>,
+}
+
+/// Handle to observe a supervised connection from other tasks.
+#[derive(Clone)]
+pub struct SupervisorHandle {
+    /// Receiver of state changes
+    pub state_rx: watch::Receiver<ConnectionState>,
+
+    /// Counters
+    pub stats: Arc<ConnectionStats>,
+}
+
+impl Supervisor {
+    /// Create a new supervisor along with its handle for observing.
+    ///
+    /// # Arguments
+    /// * `policy` - backoff policy
+    pub fn new(policy: BackoffPolicy) -> (Self, SupervisorHandle) {
+        let (state_tx, state_rx) = watch::channel(ConnectionState::Connecting);
+        let stats = Arc::new(ConnectionStats::default());
+        let handle = SupervisorHandle { state_rx, stats: stats.clone() };
+        (Self { policy, attempt: 0, state_tx, stats }, handle)
+    }
+
+    /// Set state of connection.
+    /// Setting `Streaming` state resets consecutive failed attempts.
+    ///
+    /// # Arguments
+    /// * `state` - new state
+    pub fn set_state(&mut self, state: ConnectionState) {
+        if state == ConnectionState::Streaming {
+            self.attempt = 0;
+            self.stats.connects.fetch_add(1, Ordering::Relaxed);
+        }
+        // ignore error, no one observing is fine
+        let _ = self.state_tx.send(state);
+    }
+
+    /// Record failed connection attempt.
+    pub fn record_connect_failure(&self) {
+        self.stats.connect_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that connection was declared dead by liveness watchdog.
+    pub fn record_liveness_timeout(&self) {
+        self.stats.liveness_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record measured round-trip time of ping/pong.
+    ///
+    /// # Arguments
+    /// * `rtt` - round-trip time
+    pub fn record_rtt(&self, rtt: Duration) {
+        self.stats.last_rtt_ms.store(rtt.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Get the last measured round-trip time of ping/pong in milliseconds.
+    pub fn last_rtt_ms(&self) -> u64 {
+        self.stats.last_rtt_ms.load(Ordering::Relaxed)
+    }
+
+    /// Go into backoff state, and wait for the computed delay before
+    /// returning so caller can attempt to connect again.
+    ///
+    /// # Returns
+    /// `Err` with `OperationError::ErrorWssConnect` if consecutive attempts
+    /// exceed maximum retries of policy; state is set to `Stopped`.
+    pub async fn backoff(&mut self) -> Result<(), OperationError> {
+        self.attempt = self.attempt.saturating_add(1);
+        if let Some(max_retries) = self.policy.max_retries {
+            if self.attempt > max_retries {
+                self.set_state(ConnectionState::Stopped);
+                ret_err!(OperationError::ErrorWssConnect, "gave up after {} consecutive retries", max_retries);
+            }
+        }
+
+        let delay = self.policy.delay(self.attempt);
+        self.stats.reconnects.fetch_add(1, Ordering::Relaxed);
+        self.set_state(ConnectionState::Backoff { attempt: self.attempt, delay });
+        tokio::time::sleep(delay).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_retries: Option<u32>) -> BackoffPolicy {
+        BackoffPolicy {
+            initial_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_millis(60_000),
+            multiplier: 2.0,
+            max_retries,
+        }
+    }
+
+    #[test]
+    fn delay_is_within_jitter_bounds_of_each_attempt() {
+        let policy = policy(None);
+        for (attempt, base_ms) in [(1, 1000), (2, 2000), (3, 4000), (6, 32_000), (7, 60_000), (100, 60_000), (u32::MAX, 60_000)] {
+            for _ in 0..100 {
+                let delay = policy.delay(attempt);
+                assert!(delay >= Duration::from_millis(base_ms / 2), "attempt {}: {:?}", attempt, delay);
+                assert!(delay <= Duration::from_millis(base_ms), "attempt {}: {:?}", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn delay_of_attempt_zero_is_that_of_first() {
+        let delay = policy(None).delay(0);
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn backoff_gives_up_after_max_retries() {
+        let policy = BackoffPolicy { initial_delay: Duration::from_millis(1), max_delay: Duration::from_millis(1), ..policy(Some(2)) };
+        let (mut supervisor, handle) = Supervisor::new(policy);
+        assert!(supervisor.backoff().await.is_ok());
+        assert!(supervisor.backoff().await.is_ok());
+        assert!(supervisor.backoff().await.is_err());
+        assert_eq!(*handle.state_rx.borrow(), ConnectionState::Stopped);
+        assert_eq!(handle.stats.reconnects.load(Ordering::Relaxed), 2);
+    }
+}
@@ -1,4 +1,12 @@
 use crate::deserialize::de_string_to_number;
+use crate::supervisor::BackoffPolicy;
+use crate::filter::SinkFilter;
+use crate::liquidation::Liquidation;
+
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+
+use std::time::Duration;
 
 /// Variant of type of response we expect to use in this application.
 #[derive(Debug, serde::Deserialize)]
@@ -42,6 +50,7 @@ pub struct ResponseRequestField {
 
 /// Generic topic as response with varying data for various operation
 #[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
 pub struct GenericTopic<T> {
     pub topic: String,
     pub data: GenericData<T>,
@@ -53,38 +62,300 @@ pub struct GenericTopic<T> {
 #[serde(untagged)]
 pub enum GenericData<T> {
     Liquidation(T),
+
+    /// Array of liquidation as used by v5 protocol
+    Liquidations(Vec<T>),
 }
 
-// we don't need to process anything of this field, thus we don't need to
-// convert into specific type e.g. number, but just String for displaying onto
-// console, otherwise we can do it with deserialize_with="fn".
-/// Bybit's liquidation data
+/// Bybit's liquidation data as received
 #[derive(Debug, serde::Deserialize)]
 pub struct BybitLiquidationData {
     /// Symbol; ticker
     pub symbol: String,
 
     /// Buy side, or sell side
-    pub side: String,
-    
+    pub side: Side,
+
     /// Bankruptcy price
     #[serde(deserialize_with = "de_string_to_number")]
-    pub price: f64,
+    pub price: Decimal,
 
-    /// Quantity
+    /// Quantity; fractional for linear contracts e.g. 0.025 BTC
     #[serde(deserialize_with = "de_string_to_number")]
-    pub qty: u32,   // maximum of trading qty depends on asset, but this would be suffice e.g. BTCUSD maxed at 1,000,000
+    pub qty: Decimal,
 
     /// Timestamp in milliseconds
     pub time: u64
 }
 
+/// Version of Bybit's websocket protocol to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// Legacy v2; single `liquidation` topic for all symbols with `data`
+    /// as a single object. Deprecated by Bybit.
+    V2,
+
+    /// v5; per-category endpoints with per-symbol `allLiquidation.{symbol}`
+    /// topics whose `data` is an array.
+    V5,
+}
+
+/// Category of contract on Bybit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractCategory {
+    /// e.g. BTCUSD
+    InversePerpetual,
+
+    /// USDT, or USDC perpetual e.g. BTCUSDT, BTCPERP
+    LinearPerpetual,
+
+    /// e.g. BTCUSDM22, ETHUSD0325
+    InverseFutures,
+
+    /// USDT, or USDC futures e.g. BTCUSDT-27DEC24, BTC-27DEC24
+    LinearFutures,
+}
+
+/// Side of liquidation as notified by exchange. It is the side of position
+/// liquidated, not of the order closing it; `Buy` is of long position, and
+/// `Sell` of short one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Side of position liquidated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionSide {
+    Long,
+    Short,
+}
+
+/// Kind of contract as told apart by how it is margined, and settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractKind {
+    /// Settled in base coin e.g. BTCUSD
+    InversePerpetual,
+
+    /// Settled in base coin e.g. BTCUSDM22
+    InverseFutures,
+
+    /// Settled in USDT e.g. BTCUSDT
+    LinearPerpetual,
+
+    /// Settled in USDT e.g. BTCUSDT-27DEC24
+    LinearFutures,
+
+    /// Settled in USDC e.g. BTCPERP
+    UsdcPerpetual,
+
+    /// Settled in USDC e.g. BTC-27DEC24
+    UsdcFutures,
+}
+
+/// Currency margin, and PnL of contract are settled in.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum SettleCurrency {
+    Usdt,
+    Usdc,
+
+    /// Base coin of inverse contract e.g. BTC
+    Coin(String),
+}
+
+/// Bybit's websocket endpoint to connect to.
+/// Each endpoint is served by its own connection with its own reconnect loop
+/// and heartbeat.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    /// Name of endpoint, used as prefix in logging
+    pub name: &'static str,
+
+    /// Websocket url
+    pub url: &'static str,
+
+    /// Protocol version this endpoint speaks
+    pub protocol: ProtocolVersion,
+
+    /// Topics to subscribe to
+    pub topics: Vec<String>,
+}
+
+/// Configuration of streaming session of each endpoint.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Interval of sending ping message
+    pub heartbeat_interval: Duration,
+
+    /// Duration without pong, nor data frame before connection is declared
+    /// dead and reconnected
+    pub liveness_timeout: Duration,
+
+    /// Duration to wait for acknowledgement of each subscription request
+    pub subscribe_timeout: Duration,
+
+    /// What to do when subscription is rejected
+    pub subscribe_failure_policy: SubscribeFailurePolicy,
+}
+
+/// Policy when subscription request is rejected by exchange.
+/// Timeout waiting for acknowledgement is always retried as it is likely
+/// transient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscribeFailurePolicy {
+    /// Reconnect with backoff, and subscribe again
+    Retry,
+
+    /// Stop the endpoint
+    Abort,
+}
+
+/// Configuration of notification delivery.
+#[derive(Debug, Clone)]
+pub struct DeliveryConfig {
+    /// Number of messages allowed per minute to the sink
+    pub rate_per_minute: u32,
+
+    /// Number of messages allowed to be sent in a burst to the sink
+    pub burst: u32,
+
+    /// Maximum number of notifications waiting to be sent before
+    /// `backlog_policy` kicks in
+    pub backlog_max: usize,
+
+    /// What to do when backlog exceeds `backlog_max`
+    pub backlog_policy: BacklogPolicy,
+
+    /// Maximum number of attempts to send a notification which failed
+    /// transiently
+    pub max_attempts: u32,
+
+    /// Backoff between attempts of sending a notification which failed
+    /// transiently
+    pub retry_backoff: BackoffPolicy,
+
+    /// Maximum age of notification after which it is discarded rather than
+    /// sent late, `None` for no limit
+    pub max_age: Option<Duration>,
+
+    /// Filter of notifications to be delivered
+    pub filter: SinkFilter,
+
+    /// How time of liquidation is displayed in messages
+    pub time_display: TimeDisplay,
+}
+
+/// How timestamps are displayed in messages to human.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeDisplay {
+    /// Timezone to display in e.g. `Asia/Bangkok`
+    pub timezone: Tz,
+
+    /// Format as of chrono's strftime e.g. `%Y-%m-%d %H:%M:%S%.3f %Z`
+    pub format: String,
+}
+
+/// Policy when backlog of notifications exceeds its bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklogPolicy {
+    /// Drop the notification with the smallest worth
+    DropSmallest,
+
+    /// Merge adjacent notifications into a single message
+    Merge,
+
+    /// Remove the notification with the smallest worth, and count it into
+    /// a summary message sent once backlog is drained
+    Summarize,
+
+    /// Evict the oldest notification to dead-letter of the sink. It never
+    /// summarizes, nor merges, so every event sent is as it happened, as
+    /// machine consumers expect.
+    DeadLetter,
+}
+
+/// Security of connection to SMTP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Upgrade plain connection with STARTTLS
+    StartTls,
+
+    /// Implicit TLS from the start of connection
+    Tls,
+
+    /// Plain connection without encryption e.g. to local test server
+    Plain,
+}
+
+/// Version of MQTT protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MqttVersion {
+    /// MQTT 3.1.1
+    V311,
+
+    /// MQTT 5
+    V5,
+}
+
+/// Subscription request to be sent to websocket.
+#[derive(Debug, Clone)]
+pub struct SubscribeRequest {
+    /// Request id to match with its acknowledgement
+    pub req_id: String,
+
+    /// Topics to subscribe to
+    pub args: Vec<String>,
+
+    /// Text message to send
+    pub message: String,
+}
+
+/// Response of a request (e.g. ping, subscribe) normalized across protocol
+/// versions.
+#[derive(Debug)]
+pub struct OperationResponse {
+    /// Operation
+    pub op: String,
+
+    /// Request id as sent along with the request; only v5 echoes it back
+    pub req_id: Option<String>,
+
+    /// Arguments of the request; only v2 echoes them back
+    pub args: Option<Vec<String>>,
+
+    /// Whether or not request is success
+    pub success: bool,
+
+    /// Returned message
+    pub ret_msg: Option<String>,
+
+    /// Connection id as string
+    pub conn_id: String,
+}
+
+/// Message received from websocket normalized across protocol versions.
+#[derive(Debug)]
+pub enum StreamMessage {
+    /// Response of a request
+    Response(OperationResponse),
+
+    /// Liquidations; v2 always has one, v5 can have several
+    Liquidations(Vec<Liquidation>),
+}
+
 /// Possible errors as might occur during the operation of the application.
 /// Each one contain optional `String` describing more detail for such error.
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum OperationError {
     ErrorInternalGeneric(Option<String>),
     ErrorMissingRequiredEnvVar(Option<String>),
     ErrorWssConnect(Option<String>),
     ErrorWssTopicSubscription(Option<String>),
     ErrorInternalSyncCommunication(Option<String>),
+    ErrorOutboxIo(Option<String>),
 }
@@ -1,11 +1,20 @@
-use crate::types::OperationError;
+use crate::types::{OperationError, ContractCategory, TimeDisplay};
 
+use chrono_tz::Tz;
 use tungstenite::handshake::client::Response;
 use tokio_tungstenite::{connect_async, WebSocketStream, MaybeTlsStream};
 use tokio::net::TcpStream;
 use regex::Regex;
+use rust_decimal::Decimal;
 use url::Url;
 
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Symbol of inverse futures e.g. BTCUSDM22, ETHUSD0325
+static NON_PERPETUAL_REGEX: OnceLock<Regex> = OnceLock::new();
+
 /// Get the base currency of the specified symbol.
 ///
 /// # Arguments
@@ -15,10 +24,23 @@ use url::Url;
 /// Return the reference to the string thus no allocation need as the source
 /// string is still around. This is reason not to return `String`.
 pub fn get_base_currency(symbol: &str) -> Result<&str, ()> {
+    // USDC perpetual e.g. BTCPERP
+    if let Some(base) = symbol.strip_suffix("PERP") {
+        return Ok(base);
+    }
+
+    // futures with delivery date suffix; USDT ones e.g. BTCUSDT-27DEC24 have
+    // quote currency in the pair, while USDC ones e.g. BTC-27DEC24 don't
+    let symbol = match symbol.split_once('-') {
+        Some((pair, _)) if !pair.contains("USD") => return Ok(pair),
+        Some((pair, _)) => pair,
+        None => symbol,
+    };
+
     // it can be USD, USDT, USDM..., USD<numberic>..., so USD is suffice for the
     // search.
     let matches: Vec<_> = symbol.match_indices("USD").collect();
-    if matches.len() == 0 {
+    if matches.is_empty() {
         // FIXME: add app's level error case
         return Err(());
     }
@@ -35,8 +57,7 @@ pub fn get_base_currency(symbol: &str) -> Result<&str, ()> {
 /// 3. Inverse Futures
 ///
 /// Only 2. is the linear perpetual, others are not.
-/// Currently only USDT would be applied for Bybit, and thus considered
-/// a linear perpetual (thus the name of USDT Perpetual).
+/// USDT, and USDC perpetual e.g. BTCPERP are considered a linear perpetual.
 ///
 /// # Arguments
 /// * `symbol` - fully qualified symbol to check whether it is a linear perpetual
@@ -44,7 +65,7 @@ pub fn get_base_currency(symbol: &str) -> Result<&str, ()> {
 /// # Returns
 /// True if it is a linear one, otherwise false.
 pub fn is_linear_perpetual(symbol: &str) -> bool {
-    symbol.match_indices("USDT").collect::<Vec<_>>().len() == 1    
+    symbol.match_indices("USDT").collect::<Vec<_>>().len() == 1 || symbol.ends_with("PERP")
 }
 
 /// Determine the specified symbol whether it is non-perpetual contract or not.
@@ -60,22 +81,155 @@ pub fn is_non_perpetual_contract(symbol: &str) -> bool {
     }
 
     // it could be BTCUSDM22, ETHUSD0325, etc
-    let regex = Regex::new(r"\S+USD\S\S+").unwrap();
+    let regex = NON_PERPETUAL_REGEX.get_or_init(|| Regex::new(r"\S+USD\S\S+").unwrap());
     regex.is_match(symbol)
 }
 
-/// Get the milliseconds and nanoseconds pair from the specified timestamp in
-/// milliseconds.
+/// Get the contract category of the specified symbol.
+///
+/// # Arguments
+/// * `symbol` - fully qualified symbol
+pub fn get_contract_category(symbol: &str) -> ContractCategory {
+    // delivery date suffix goes first as USDT futures e.g. BTCUSDT-27DEC24
+    // otherwise look like USDT perpetual
+    if symbol.contains('-') {
+        ContractCategory::LinearFutures
+    }
+    else if is_linear_perpetual(symbol) {
+        ContractCategory::LinearPerpetual
+    }
+    else if is_non_perpetual_contract(symbol) {
+        ContractCategory::InverseFutures
+    }
+    else {
+        ContractCategory::InversePerpetual
+    }
+}
+
+/// Format worth rounded to 3 decimal places with thousands separators.
+///
+/// # Arguments
+/// * `worth` - worth in USD
+pub fn format_worth(worth: Decimal) -> String {
+    format_decimal(worth.round_dp(3).normalize())
+}
+
+/// Format decimal with thousands separators, keeping all of its decimal
+/// places e.g. `0.0250` stays as is, and `64000.5` becomes `64,000.5`.
 ///
 /// # Arguments
-/// * `ms_timestamp` - timestamp in milliseconds
+/// * `value` - decimal e.g. price, or quantity as of exchange
+pub fn format_decimal(value: Decimal) -> String {
+    let value_str = value.to_string();
+    let (sign, unsigned) = match value_str.strip_prefix('-') {
+        Some(res) => ("-", res),
+        None => ("", value_str.as_str()),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let mut res = sign.to_owned();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            res.push(',');
+        }
+        res.push(c);
+    }
+    if let Some(fraction) = fraction {
+        res.push('.');
+        res.push_str(fraction);
+    }
+    res
+}
+
+/// Format size of position in coin with thousands separators. Size below 1
+/// keeps up to 8 decimal places so that of small position doesn't round to
+/// zero, otherwise 3 decimal places.
+///
+/// # Arguments
+/// * `size` - size e.g. in BTC
+pub fn format_size(size: Decimal) -> String {
+    let decimal_places = if size.abs() >= Decimal::ONE { 3 } else { 8 };
+    format_decimal(size.round_dp(decimal_places).normalize())
+}
+
+/// Encode bytes as lowercase hex string.
+///
+/// # Arguments
+/// * `bytes` - bytes to encode
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Get value of environment variable parsed into the target type, or the
+/// default value if such environment variable is not defined.
+///
+/// # Arguments
+/// * `name` - name of environment variable
+/// * `default` - default value to use if not defined
 ///
 /// # Returns
-/// Pair of seconds, and nanoseconds representing the input (ms) timestamp.
-pub fn get_ms_and_ns_pair(ms_timestamp: u64) -> (u64, u32) {
-    let ms: u64 = ms_timestamp / 1000;
-    let ns: u32 = (ms_timestamp % 1000) as u32;
-    (ms, ns)
+/// `Err` with `OperationError::ErrorInternalGeneric` if defined but cannot
+/// be parsed.
+pub fn get_env_var_or<T>(name: &str, default: T) -> Result<T, OperationError>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Display
+{
+    match std::env::var(name) {
+        Ok(val) => match val.parse::<T>() {
+            Ok(res) => Ok(res),
+            Err(e) => ret_err!(OperationError::ErrorInternalGeneric, "cannot parse environment variable {}='{}'; err={}", name, val, e),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+/// Default format of time in messages e.g. `2024-03-05 07:14:05.748 UTC`
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f %Z";
+
+/// Get display of time of the sink from environment variables
+/// `HX_BYBIT_SHIPREKT_<SINK>_TIMEZONE`, and
+/// `HX_BYBIT_SHIPREKT_<SINK>_TIME_FORMAT`, falling back to
+/// `HX_BYBIT_SHIPREKT_TIMEZONE`, and `HX_BYBIT_SHIPREKT_TIME_FORMAT`
+/// respectively; UTC, and `DEFAULT_TIME_FORMAT` if none is defined.
+///
+/// # Arguments
+/// * `sink_env_name` - name of sink as in environment variables e.g. `SLACK`
+pub fn time_display_from_env(sink_env_name: &str) -> Result<TimeDisplay, OperationError> {
+    let timezone = get_env_var_or(&format!("HX_BYBIT_SHIPREKT_{}_TIMEZONE", sink_env_name),
+                                  get_env_var_or("HX_BYBIT_SHIPREKT_TIMEZONE", Tz::UTC)?)?;
+    let format = std::env::var(format!("HX_BYBIT_SHIPREKT_{}_TIME_FORMAT", sink_env_name))
+        .or_else(|_| std::env::var("HX_BYBIT_SHIPREKT_TIME_FORMAT"))
+        .unwrap_or_else(|_| DEFAULT_TIME_FORMAT.to_owned());
+    TimeDisplay::new(timezone, &format)
+}
+
+/// Parse comma-separated list of symbols.
+/// Whitespaces are trimmed, empty entries are ignored, and symbols are
+/// uppercased.
+///
+/// # Arguments
+/// * `list_str` - comma-separated list of symbols e.g. "BTCUSDT, ETHUSDT"
+pub fn parse_symbol_list(list_str: &str) -> Vec<String> {
+    list_str.split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse comma-separated list of contract categories.
+///
+/// # Arguments
+/// * `list_str` - comma-separated list e.g. "linear_perpetual, inverse_futures"
+pub fn parse_category_list(list_str: &str) -> Result<Vec<ContractCategory>, OperationError> {
+    list_str.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<ContractCategory>())
+        .collect()
 }
 
 /// Connect to specified websocket url.
@@ -93,3 +247,44 @@ pub async fn connect_async_to_wss(wss_url: &str) -> Result<(WebSocketStream<Mayb
         Err(e) => ret_err!(OperationError::ErrorWssConnect, "cannot connect to WSS; err={}", e),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contract_category_of_each_kind_of_symbol() {
+        assert_eq!(get_contract_category("BTCUSD"), ContractCategory::InversePerpetual);
+        assert_eq!(get_contract_category("BTCUSDM22"), ContractCategory::InverseFutures);
+        assert_eq!(get_contract_category("ETHUSD0325"), ContractCategory::InverseFutures);
+        assert_eq!(get_contract_category("BTCUSDT"), ContractCategory::LinearPerpetual);
+        assert_eq!(get_contract_category("1000PEPEUSDT"), ContractCategory::LinearPerpetual);
+        assert_eq!(get_contract_category("BTCPERP"), ContractCategory::LinearPerpetual);
+        assert_eq!(get_contract_category("BTCUSDT-27DEC24"), ContractCategory::LinearFutures);
+        assert_eq!(get_contract_category("BTC-27DEC24"), ContractCategory::LinearFutures);
+    }
+
+    #[test]
+    fn base_currency_of_each_kind_of_symbol() {
+        assert_eq!(get_base_currency("BTCUSD"), Ok("BTC"));
+        assert_eq!(get_base_currency("BTCUSDM22"), Ok("BTC"));
+        assert_eq!(get_base_currency("1000PEPEUSDT"), Ok("1000PEPE"));
+        assert_eq!(get_base_currency("BTCPERP"), Ok("BTC"));
+        assert_eq!(get_base_currency("BTCUSDT-27DEC24"), Ok("BTC"));
+        assert_eq!(get_base_currency("BTC-27DEC24"), Ok("BTC"));
+        assert_eq!(get_base_currency("BTCEUR"), Err(()));
+    }
+
+    #[test]
+    fn time_display_from_env_rejects_unknown_timezone_and_format() {
+        std::env::set_var("HX_BYBIT_SHIPREKT_TEST_TZ_TIMEZONE", "Asia/Bangkok");
+        let display = time_display_from_env("TEST_TZ").unwrap();
+        assert_eq!(display.format_ms(1_700_000_000_748), "2023-11-15 05:13:20.748 +07");
+
+        std::env::set_var("HX_BYBIT_SHIPREKT_TEST_BAD_TZ_TIMEZONE", "Mars/Olympus");
+        assert!(time_display_from_env("TEST_BAD_TZ").is_err());
+
+        std::env::set_var("HX_BYBIT_SHIPREKT_TEST_BAD_FORMAT_TIME_FORMAT", "%H:%M %Q");
+        assert!(time_display_from_env("TEST_BAD_FORMAT").is_err());
+    }
+}
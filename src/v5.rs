@@ -0,0 +1,93 @@
+use crate::deserialize::de_string_to_number;
+use crate::types::{GenericTopic, BybitLiquidationData, Side};
+
+use rust_decimal::Decimal;
+
+/// Linear contracts (USDT/USDC perpetual, and futures) public endpoint
+pub const LINEAR_URL: &str = "wss://stream.bybit.com/v5/public/linear";
+
+/// Inverse contracts (inverse perpetual, and inverse futures) public endpoint
+pub const INVERSE_URL: &str = "wss://stream.bybit.com/v5/public/inverse";
+
+/// Prefix of liquidation topic, to be followed by symbol
+pub const LIQUIDATION_TOPIC_PREFIX: &str = "allLiquidation";
+
+/// Maximum number of topics in a single subscription request
+pub const MAX_SUBSCRIBE_ARGS: usize = 10;
+
+/// Variant of type of response we expect to use from v5 protocol.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum VariantResponse {
+    /// Cover all auxilary type of response; check `op` field to differentiate
+    /// the type of response
+    Response(RequestResponse),
+
+    /// Liquidation
+    Liquidation(GenericTopic<BybitAllLiquidationData>),
+}
+
+/// Request's response.
+/// Different from v2, `op` is at the top level and there is no echoed
+/// request object.
+#[derive(Debug, serde::Deserialize)]
+pub struct RequestResponse {
+    /// Whether or not request is success
+    pub success: bool,
+
+    /// Returned message
+    pub ret_msg: Option<String>,
+
+    /// Connection id as string
+    pub conn_id: String,
+
+    /// Request id as sent along with the request (if any)
+    pub req_id: Option<String>,
+
+    /// Operation
+    pub op: String,
+}
+
+/// Bybit's v5 liquidation data of `allLiquidation` topic
+#[derive(Debug, serde::Deserialize)]
+pub struct BybitAllLiquidationData {
+    /// Timestamp in milliseconds
+    #[serde(rename = "T")]
+    pub time: u64,
+
+    /// Symbol; ticker
+    #[serde(rename = "s")]
+    pub symbol: String,
+
+    /// Buy side, or sell side
+    #[serde(rename = "S")]
+    pub side: Side,
+
+    /// Quantity
+    #[serde(rename = "v", deserialize_with = "de_string_to_number")]
+    pub qty: Decimal,
+
+    /// Bankruptcy price
+    #[serde(rename = "p", deserialize_with = "de_string_to_number")]
+    pub price: Decimal,
+}
+
+impl From<BybitAllLiquidationData> for BybitLiquidationData {
+    fn from(data: BybitAllLiquidationData) -> Self {
+        Self {
+            symbol: data.symbol,
+            side: data.side,
+            price: data.price,
+            qty: data.qty,
+            time: data.time,
+        }
+    }
+}
+
+/// Get the liquidation topic of the specified symbol.
+///
+/// # Arguments
+/// * `symbol` - fully qualified symbol e.g. BTCUSDT
+pub fn liquidation_topic(symbol: &str) -> String {
+    format!("{}.{}", LIQUIDATION_TOPIC_PREFIX, symbol)
+}
@@ -0,0 +1,111 @@
+use tokio::time::Instant;
+
+use std::time::Duration;
+
+/// Liveness watchdog of a websocket connection.
+/// It tracks last pong and last data frame received, so the connection can
+/// be declared dead when nothing comes back within silence window even
+/// though the underlying socket still looks open.
+pub struct Watchdog {
+    silence_window: Duration,
+    last_pong: Instant,
+    last_data: Instant,
+    ping_sent_at: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Create a new watchdog starting its silence window from now.
+    ///
+    /// # Arguments
+    /// * `silence_window` - duration without pong, and data frame before
+    ///   connection is declared dead
+    /// * `now` - current instant
+    pub fn new(silence_window: Duration, now: Instant) -> Self {
+        Self { silence_window, last_pong: now, last_data: now, ping_sent_at: None }
+    }
+
+    /// Record that ping has been sent.
+    /// Only the first ping since the last pong is recorded, so round-trip time
+    /// won't be under-measured if pong is late.
+    ///
+    /// # Arguments
+    /// * `now` - instant in which ping is sent
+    pub fn on_ping_sent(&mut self, now: Instant) {
+        if self.ping_sent_at.is_none() {
+            self.ping_sent_at = Some(now);
+        }
+    }
+
+    /// Record that pong has been received.
+    ///
+    /// # Arguments
+    /// * `now` - instant in which pong is received
+    ///
+    /// # Returns
+    /// Round-trip time since the ping was sent, or `None` if no ping has been
+    /// recorded as sent.
+    pub fn on_pong(&mut self, now: Instant) -> Option<Duration> {
+        self.last_pong = now;
+        self.ping_sent_at.take().map(|sent_at| self.last_pong.duration_since(sent_at))
+    }
+
+    /// Record that data frame has been received.
+    ///
+    /// # Arguments
+    /// * `now` - instant in which data frame is received
+    pub fn on_data(&mut self, now: Instant) {
+        self.last_data = now;
+    }
+
+    /// Get the instant in which connection will be declared dead if nothing
+    /// else is received before then.
+    pub fn deadline(&self) -> Instant {
+        self.last_pong.max(self.last_data) + self.silence_window
+    }
+
+    /// Get the duration since the last pong, or data frame whichever is
+    /// the latest.
+    ///
+    /// # Arguments
+    /// * `now` - current instant
+    pub fn silence(&self, now: Instant) -> Duration {
+        now.duration_since(self.last_pong.max(self.last_data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SILENCE_WINDOW: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn deadline_is_silence_window_after_the_latest_of_pong_and_data() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(SILENCE_WINDOW, start);
+        assert_eq!(watchdog.deadline(), start + SILENCE_WINDOW);
+
+        watchdog.on_data(start + Duration::from_secs(10));
+        assert_eq!(watchdog.deadline(), start + Duration::from_secs(40));
+
+        watchdog.on_pong(start + Duration::from_secs(25));
+        assert_eq!(watchdog.deadline(), start + Duration::from_secs(55));
+
+        // data frame older than the last pong doesn't move deadline back
+        watchdog.on_data(start + Duration::from_secs(20));
+        assert_eq!(watchdog.deadline(), start + Duration::from_secs(55));
+        assert_eq!(watchdog.silence(start + Duration::from_secs(31)), Duration::from_secs(6));
+    }
+
+    #[test]
+    fn round_trip_is_measured_from_the_first_ping_since_the_last_pong() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(SILENCE_WINDOW, start);
+        assert_eq!(watchdog.on_pong(start), None);
+
+        watchdog.on_ping_sent(start + Duration::from_secs(1));
+        watchdog.on_ping_sent(start + Duration::from_secs(3));
+        assert_eq!(watchdog.on_pong(start + Duration::from_secs(4)), Some(Duration::from_secs(3)));
+        assert_eq!(watchdog.on_pong(start + Duration::from_secs(5)), None);
+    }
+}